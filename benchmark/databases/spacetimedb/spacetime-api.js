@@ -37,6 +37,7 @@ export const spacetimeMetrics = {
     // Reducer metrics
     reducerCalls: new Counter('spacetime_reducer_calls_total'),
     reducerErrors: new Counter('spacetime_reducer_errors_total'),
+    reducerRateLimited: new Counter('spacetime_reducer_rate_limited_total'),
     reducerLatency: new Trend('spacetime_reducer_latency_ms'),
 
     // Table metrics
@@ -436,6 +437,15 @@ export function createConnection(config = null) {
 // HTTP API Methods
 // ============================================================================
 
+/**
+ * Whether a reducer call was rejected by the module's rate limiter
+ * @param {Object} response - k6 HTTP response
+ * @returns {boolean} True if the body carries a `Rate limited:` error
+ */
+function isRateLimited(response) {
+    return Boolean(response.body) && String(response.body).includes('Rate limited:');
+}
+
 /**
  * Call a reducer via HTTP API
  * @param {string} reducer - Reducer name
@@ -494,6 +504,12 @@ export function callReducerHttp(reducer, args = [], config = null) {
             };
         }
 
+        if (isRateLimited(response)) {
+            // Failed reducers come back as 5xx too, but a rate-limited call
+            // would only be limited again - don't retry it
+            break;
+        }
+
         if (response.status >= 500) {
             // Server error - retry
             retries++;
@@ -509,6 +525,9 @@ export function callReducerHttp(reducer, args = [], config = null) {
 
     timer.stopWithError('validation', 'reducer');
     spacetimeMetrics.reducerErrors.add(1);
+    if (isRateLimited(response)) {
+        spacetimeMetrics.reducerRateLimited.add(1);
+    }
 
     check(response, {
         'Reducer call failed': (r) => false,
@@ -648,19 +667,56 @@ export function incrementCounter(counterId, amount = 1, useWebSocket = false, co
     return callReducerHttp('increment', args, config);
 }
 
+/**
+ * Seed zeroed counters named `${prefix}${index}` for the scaling benchmark
+ * Seeds in chunks so no single reducer call has to insert the whole table
+ * @param {string} prefix - Counter name prefix
+ * @param {number} total - Total number of counters to seed
+ * @param {number} chunkSize - Counters inserted per reducer call
+ * @param {Object} config - Configuration object
+ * @returns {Object} Seeding result
+ */
+export function seedCounters(prefix, total, chunkSize = 10000, config = null) {
+    const timer = createTimer();
+    let seeded = 0;
+
+    for (let start = 0; start < total; start += chunkSize) {
+        const count = Math.min(chunkSize, total - start);
+        const result = callReducerHttp('seed_counters', [prefix, start, count], config);
+
+        if (!result.success) {
+            return {
+                success: false,
+                seeded: seeded,
+                error: result.error,
+            };
+        }
+
+        seeded += count;
+    }
+
+    return {
+        success: true,
+        seeded: seeded,
+        duration: timer.stop('batch', 0, seeded),
+    };
+}
+
 /**
  * Create a message
+ * The generated message id is sent as the idempotency key, so HTTP retries
+ * (maxRetries) of the same call insert the message at most once
  * @param {string} content - Message content
- * @param {string} sender - Sender identifier
+ * @param {?string} sender - Sender display name, or null for none
  * @param {boolean} useWebSocket - Use WebSocket instead of HTTP
  * @param {Object} connection - WebSocket connection (if using WebSocket)
  * @param {Object} config - Configuration object
+ * @param {string} channel - Channel to post in
  * @returns {Object} Operation result
  */
-export function createMessage(content, sender, useWebSocket = false, connection = null, config = null) {
+export function createMessage(content, sender, useWebSocket = false, connection = null, config = null, channel = 'benchmark') {
     const messageId = randomUUID();
-    const timestamp = new Date().toISOString();
-    const args = [messageId, sender, content, timestamp];
+    const args = [optionArg(sender), content, channel, optionArg(messageId)];
 
     if (useWebSocket && connection) {
         return connection.callReducer('create_message', args);
@@ -691,6 +747,77 @@ export function readCounter(counterId, config = null) {
     return queryHttp(query, config);
 }
 
+/**
+ * Encode an optional reducer argument the way SpacetimeDB's JSON format expects
+ * @param {*} value - Value, or null/undefined for none
+ * @returns {Object} `{ some: value }` or `{ none: [] }`
+ */
+export function optionArg(value) {
+    return value === null || value === undefined ? { none: [] } : { some: value };
+}
+
+/**
+ * Fetch a page of channel messages via the get_messages reducer
+ * Mirrors Convex getMessagesByChannel; the page lands in message_page under
+ * this VU's request id, readable through the my_message_page view
+ * @param {string} channel - Channel name
+ * @param {number} limit - Page size (max 1000)
+ * @param {Object|null} before - next_cursor from the previous page
+ * @param {boolean} useWebSocket - Use WebSocket instead of HTTP
+ * @param {Object} connection - WebSocket connection (if using WebSocket)
+ * @param {Object} config - Configuration object
+ * @returns {Object} Operation result
+ */
+export function getMessagesByChannel(channel, limit = 100, before = null, useWebSocket = false, connection = null, config = null) {
+    const args = [channel, limit, optionArg(before), __VU];
+
+    if (useWebSocket && connection) {
+        return connection.callReducer('get_messages', args);
+    }
+
+    return callReducerHttp('get_messages', args, config);
+}
+
+/**
+ * Fetch a page of a sender's messages via the get_messages_by_sender reducer
+ * Mirrors Convex getMessagesBySender; the page lands in message_page under
+ * this VU's request id, readable through the my_message_page view
+ * @param {string} sender - Sender identity (hex)
+ * @param {number} limit - Page size (max 1000)
+ * @param {Object|null} before - next_cursor from the previous page
+ * @param {boolean} useWebSocket - Use WebSocket instead of HTTP
+ * @param {Object} connection - WebSocket connection (if using WebSocket)
+ * @param {Object} config - Configuration object
+ * @returns {Object} Operation result
+ */
+export function getMessagesBySender(sender, limit = 100, before = null, useWebSocket = false, connection = null, config = null) {
+    const args = [{ __identity__: sender }, limit, optionArg(before), __VU];
+
+    if (useWebSocket && connection) {
+        return connection.callReducer('get_messages_by_sender', args);
+    }
+
+    return callReducerHttp('get_messages_by_sender', args, config);
+}
+
+/**
+ * Look up a counter via the get_counter reducer
+ * Mirrors Convex getCounterByName; the result lands in counter_lookup under
+ * this VU's request id, readable through the my_counter_lookup view
+ * @param {string} name - Counter name
+ * @param {boolean} useWebSocket - Use WebSocket instead of HTTP
+ * @param {Object} connection - WebSocket connection (if using WebSocket)
+ * @param {Object} config - Configuration object
+ * @returns {Object} Operation result
+ */
+export function getCounter(name, useWebSocket = false, connection = null, config = null) {
+    if (useWebSocket && connection) {
+        return connection.callReducer('get_counter', [name, __VU]);
+    }
+
+    return callReducerHttp('get_counter', [name, __VU], config);
+}
+
 // ============================================================================
 // Batch Operations
 // ============================================================================
@@ -849,12 +976,17 @@ export default {
     callReducerHttp,
     queryHttp,
     getSchema,
+    optionArg,
 
     // High-level operations
     incrementCounter,
+    seedCounters,
     createMessage,
     readMessages,
     readCounter,
+    getMessagesByChannel,
+    getMessagesBySender,
+    getCounter,
 
     // Batch operations
     executeBatch,
@@ -11,6 +11,7 @@
  *   k6 run test.js
  *   k6 run -e SPACETIME_HOST=localhost -e SPACETIME_PORT=3000 test.js
  *   k6 run -e LOAD_PROFILE=tps500 test.js
+ *   k6 run -e SCENARIO=counterScaling -e COUNTER_SCALE=100k test.js
  */
 
 import { check, sleep, group } from 'k6';
@@ -23,12 +24,12 @@ import {
     recordSuccess,
     recordError,
     createTimer,
-    randomUUID,
     randomAlphanumeric,
     randomInt,
     randomString,
     randomChoice,
     generateUser,
+    getDistributedKey,
     log,
 } from '../../utils.js';
 
@@ -47,9 +48,14 @@ import {
     callReducerHttp,
     queryHttp,
     incrementCounter,
+    seedCounters,
     createMessage,
     readMessages,
     readCounter,
+    getMessagesByChannel,
+    getMessagesBySender,
+    getCounter,
+    optionArg,
     executeBatch,
     spacetimeMetrics,
 } from './spacetime-api.js';
@@ -158,6 +164,85 @@ export function counterScenario() {
     sleep(randomIntBetween(10, 100) / 1000);
 }
 
+/**
+ * Counter table sizes for the scaling benchmark
+ */
+const counterScales = {
+    '10k': 10000,
+    '100k': 100000,
+    '1m': 1000000,
+};
+
+/**
+ * Resolve the seeded counter table size from COUNTER_SCALE
+ * @returns {number} Number of counters to seed (0 when scaling is disabled)
+ */
+function getCounterScale() {
+    const scale = (__ENV.COUNTER_SCALE || '').toLowerCase();
+    if (!scale) {
+        return 0;
+    }
+    return counterScales[scale] || parseInt(scale);
+}
+
+/**
+ * Scenario 1b: Counter Increments Against a Large Table
+ * Increments random counters out of a pre-seeded table of COUNTER_SCALE rows,
+ * showing whether per-increment lookup cost stays flat as the table grows
+ */
+export function counterScalingScenario() {
+    const config = getConfig();
+    const scale = getCounterScale() || counterScales['10k'];
+    const counterId = `scale_${randomInt(0, scale - 1)}`;
+
+    group('Counter Increment (Scaling)', () => {
+        const result = callReducerHttp('increment_counter', [counterId, 1], config);
+
+        if (result.success) {
+            recordSuccess('write', result.duration, 0, 1);
+        } else {
+            recordError('validation', 'counter_scaling');
+        }
+
+        check(result, {
+            'Scaled counter increment successful': (r) => r.success,
+        });
+    });
+
+    sleep(randomIntBetween(10, 100) / 1000);
+}
+
+/**
+ * Scenario 1c: Hot-Key Counter Increments
+ * Concentrates increments on a few hot keys, either on single counter rows or
+ * striped across shards (SHARDED_COUNTERS=true) to quantify contention relief
+ */
+export function hotKeyScenario() {
+    const config = getConfig();
+    const sharded = __ENV.SHARDED_COUNTERS === 'true';
+    const hotKeyCount = parseInt(__ENV.HOT_KEY_COUNT || '5');
+    const hotKeyRatio = parseFloat(__ENV.HOT_KEY_RATIO || '0.9');
+    const counterId = getDistributedKey(hotKeyCount, hotKeyRatio, 'hot');
+
+    group(sharded ? 'Hot-Key Increment (Sharded)' : 'Hot-Key Increment', () => {
+        const result = sharded
+            ? callReducerHttp('increment_sharded_counter', [counterId, 1, __VU], config)
+            : callReducerHttp('increment_counter', [counterId, 1], config);
+
+        if (result.success) {
+            recordSuccess('write', result.duration, 0, 1);
+        } else {
+            recordError('validation', 'hot_key');
+        }
+
+        check(result, {
+            'Hot-key increment successful': (r) => r.success,
+        });
+    });
+
+    sleep(randomIntBetween(10, 100) / 1000);
+}
+
 /**
  * Scenario 2: Message Creation (Write with Larger Payload)
  * Tests write performance with larger payloads
@@ -186,12 +271,7 @@ export function messageScenario() {
             connection.subscribe('SELECT * FROM messages ORDER BY timestamp DESC LIMIT 100');
 
             // Create message via reducer
-            result = connection.callReducer('create_message', [
-                randomUUID(),
-                sender,
-                content,
-                new Date().toISOString(),
-            ]);
+            result = createMessage(content, sender, true, connection, config);
 
             connection.close();
         } else {
@@ -239,13 +319,86 @@ export function mixedScenario() {
     sleep(randomIntBetween(20, 150) / 1000);
 }
 
+/**
+ * Read leg of the `mixed` load profile
+ */
+export function readScenario() {
+    const config = getConfig();
+    const useWebSocket = __ENV.USE_WEBSOCKET !== 'false';
+
+    group('Read Operations', () => {
+        performReadOperation(config, useWebSocket);
+    });
+
+    sleep(randomIntBetween(20, 150) / 1000);
+}
+
+/**
+ * Write leg of the `mixed` load profile
+ */
+export function writeScenario() {
+    const config = getConfig();
+    const useWebSocket = __ENV.USE_WEBSOCKET !== 'false';
+
+    group('Write Operations', () => {
+        performWriteOperation(config, useWebSocket);
+    });
+
+    sleep(randomIntBetween(20, 150) / 1000);
+}
+
+/**
+ * Delete leg of the `mixed` load profile
+ * Creates a throwaway counter and deletes it, so every iteration hits the
+ * real delete path instead of a "not found" error
+ */
+export function deleteScenario() {
+    const config = getConfig();
+    const counterId = `delete_${randomAlphanumeric(16)}`;
+
+    group('Delete Operations', () => {
+        const created = callReducerHttp('increment_counter', [counterId, 1], config);
+        if (!created.success) {
+            recordError('validation', 'delete');
+            return;
+        }
+
+        const timer = createTimer();
+        const result = callReducerHttp('delete_counter', [counterId], config);
+
+        if (result.success) {
+            timer.stop('delete', 0, 1);
+            spacetimeMetrics.tableDeletes.add(1);
+        } else {
+            timer.stopWithError('validation', 'delete');
+        }
+
+        check(result, {
+            'Counter delete successful': (r) => r.success,
+        });
+    });
+
+    sleep(randomIntBetween(20, 150) / 1000);
+}
+
 /**
  * Perform a read operation
  * @param {Object} config - Configuration
  * @param {boolean} useWebSocket - Whether to use WebSocket
  */
 function performReadOperation(config, useWebSocket) {
-    const operation = randomChoice(['query_messages', 'query_counter', 'query_user']);
+    const operations = [
+        'query_messages',
+        'query_counter',
+        'query_user',
+        'messages_by_channel',
+        'get_counter',
+    ];
+    // Over HTTP our own sender identity is only known from SPACETIME_IDENTITY
+    if (useWebSocket || config.identity) {
+        operations.push('messages_by_sender');
+    }
+    const operation = randomChoice(operations);
     const timer = createTimer();
     let result;
 
@@ -282,6 +435,38 @@ function performReadOperation(config, useWebSocket) {
             break;
         }
 
+        case 'messages_by_channel':
+        case 'messages_by_sender':
+        case 'get_counter': {
+            // Same read calls the Convex benchmark issues, via query reducers
+            const limit = randomInt(10, 100);
+            let connection = null;
+            if (useWebSocket) {
+                connection = createConnection(config);
+                if (!connection.connect()) {
+                    result = { success: false, error: 'Connection failed' };
+                    break;
+                }
+            }
+
+            if (operation === 'messages_by_channel') {
+                const channel = randomChoice(['general', 'random', 'benchmark']);
+                result = getMessagesByChannel(channel, limit, null, useWebSocket, connection, config);
+            } else if (operation === 'messages_by_sender') {
+                // Messages are keyed by the posting identity, so read back our own
+                const sender = (connection && connection.identity) || config.identity;
+                result = getMessagesBySender(sender, limit, null, useWebSocket, connection, config);
+            } else {
+                const counterId = `counter_${randomInt(1, 100)}`;
+                result = getCounter(counterId, useWebSocket, connection, config);
+            }
+
+            if (connection) {
+                connection.close();
+            }
+            break;
+        }
+
         case 'query_user': {
             const userId = randomAlphanumeric(16);
             const query = `SELECT * FROM users WHERE id = '${userId}'`;
@@ -347,12 +532,7 @@ function performWriteOperation(config, useWebSocket) {
             if (useWebSocket) {
                 const connection = createConnection(config);
                 if (connection.connect()) {
-                    result = connection.callReducer('create_message', [
-                        randomUUID(),
-                        sender,
-                        content,
-                        new Date().toISOString(),
-                    ]);
+                    result = createMessage(content, sender, true, connection, config);
                     connection.close();
                 } else {
                     result = { success: false, error: 'Connection failed' };
@@ -472,6 +652,53 @@ export function batchScenario() {
     sleep(randomIntBetween(100, 500) / 1000);
 }
 
+/**
+ * Scenario 4b: Batch Insert Reducers
+ * Inserts BATCH_SIZE messages (or events, with BATCH_TABLE=event) in a single
+ * reducer call, so rows/sec can be charted against batch size
+ */
+export function batchInsertScenario() {
+    const config = getConfig();
+    const batchSize = parseInt(__ENV.BATCH_SIZE || '10');
+    const table = __ENV.BATCH_TABLE || 'message';
+
+    group('Batch Insert', () => {
+        const rows = [];
+        for (let i = 0; i < batchSize; i++) {
+            if (table === 'event') {
+                rows.push({
+                    event_type: randomChoice(['cpu_usage', 'memory_usage', 'request_count']),
+                    source: `host_${randomInt(1, 10)}`,
+                    data: { value: Math.random() * 100, unit: optionArg(null), tags: [] },
+                });
+            } else {
+                rows.push({
+                    sender_name: optionArg(`user_${randomAlphanumeric(16)}`),
+                    content: randomString(randomInt(100, 500)),
+                    channel: randomChoice(['general', 'random', 'benchmark']),
+                    priority: optionArg(null),
+                    tags: [],
+                });
+            }
+        }
+
+        const reducer = table === 'event' ? 'create_events_batch' : 'create_messages_batch';
+        const result = callReducerHttp(reducer, [rows], config);
+
+        if (result.success) {
+            recordSuccess('batch', result.duration, 0, batchSize);
+        } else {
+            recordError('validation', 'batch_insert');
+        }
+
+        check(result, {
+            'Batch insert successful': (r) => r.success,
+        });
+    });
+
+    sleep(randomIntBetween(100, 500) / 1000);
+}
+
 // ============================================================================
 // WebSocket Stress Scenario
 // ============================================================================
@@ -512,12 +739,13 @@ export function websocketStressScenario() {
                 }
 
                 case 'message': {
-                    result = connection.callReducer('create_message', [
-                        randomUUID(),
-                        `user_${randomAlphanumeric(16)}`,
+                    result = createMessage(
                         randomString(randomInt(50, 200)),
-                        new Date().toISOString(),
-                    ]);
+                        `user_${randomAlphanumeric(16)}`,
+                        true,
+                        connection,
+                        config,
+                    );
                     break;
                 }
             }
@@ -579,6 +807,19 @@ export function setup() {
 
     log('INFO', 'Successfully connected to SpacetimeDB');
 
+    // Seed the counter table for the scaling benchmark
+    const counterScale = getCounterScale();
+    if (counterScale > 0) {
+        log('INFO', `Seeding ${counterScale} counters for scaling benchmark`);
+        const seedResult = seedCounters('scale_', counterScale, 10000, config);
+        if (!seedResult.success) {
+            log('ERROR', `Counter seeding failed after ${seedResult.seeded} rows`);
+            connection.close();
+            return { healthy: false };
+        }
+        log('INFO', `Seeded ${seedResult.seeded} counters in ${seedResult.duration}ms`);
+    }
+
     // Get schema info
     const schemaResult = connection.callReducer('get_schema_info', []);
     if (schemaResult.success) {
@@ -655,12 +896,16 @@ export function teardown(data) {
  *   k6 run -e SCENARIO=counter test.js
  *   k6 run -e SCENARIO=message test.js
  *   k6 run -e SCENARIO=mixed test.js
+ *   k6 run -e SCENARIO=counterScaling -e COUNTER_SCALE=1m test.js
  */
 export const scenarios = {
     counter: counterScenario,
+    counterScaling: counterScalingScenario,
+    hotKey: hotKeyScenario,
     message: messageScenario,
     mixed: mixedScenario,
     batch: batchScenario,
+    batchInsert: batchInsertScenario,
     websocketStress: websocketStressScenario,
 };
 
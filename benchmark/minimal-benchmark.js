@@ -36,7 +36,7 @@ const CONFIG = {
         incrementEndpoint: () => `/v1/database/${CONFIG.spacetimedb.identity}/call/increment_counter`,
         messageEndpoint: () => `/v1/database/${CONFIG.spacetimedb.identity}/call/create_message`,
         incrementBody: (name) => JSON.stringify([`counter_${name}`, 1]),
-        messageBody: (sender, content) => JSON.stringify([`user_${sender}`, content, 'benchmark'])
+        messageBody: (sender, content) => JSON.stringify([{ some: `user_${sender}` }, content, 'benchmark', { none: [] }])
     }
 };
 
@@ -271,8 +271,8 @@ function spacetimeCreateMessage() {
     const content = `Message ${randomString(50)}`;
     const channel = `channel_${randomIntBetween(1, 10)}`;
 
-    // SpacetimeDB expects args as a JSON array: [sender, content, channel]
-    const payload = JSON.stringify([sender, content, channel]);
+    // SpacetimeDB expects args as a JSON array: [sender_name, content, channel, idempotency_key]
+    const payload = JSON.stringify([{ some: sender }, content, channel, { none: [] }]);
 
     const startTime = Date.now();
     const response = http.post(url, payload, {
@@ -6,6 +6,14 @@
 set -e
 
 # Configuration
+# Pass --upgrade to republish over the existing module (keeping its data)
+# and run on_module_update, instead of deleting it first. When upgrading a
+# database that has no admins yet, set BENCHMARK_OWNER_IDENTITY to the owner's
+# identity so the build lets the owner bootstrap the first admin
+UPGRADE=false
+if [ "$1" = "--upgrade" ]; then
+    UPGRADE=true
+fi
 MODULE_NAME="benchmark"
 SPACETIME_HOST="127.0.0.1:3000"
 SPACETIME_WS="ws://127.0.0.1:3000"
@@ -84,12 +92,14 @@ fi
 
 echo -e "${GREEN}SpacetimeDB build successful${NC}"
 
-# Check if module already exists and delete it
-echo ""
-echo -e "${YELLOW}Checking for existing module '${MODULE_NAME}'...${NC}"
-if spacetime list | grep -q "${MODULE_NAME}"; then
-    echo -e "${YELLOW}Module '${MODULE_NAME}' exists, deleting...${NC}"
-    spacetime delete "${MODULE_NAME}" --force 2>/dev/null || true
+# Check if module already exists and delete it (unless upgrading in place)
+if [ "${UPGRADE}" = false ]; then
+    echo ""
+    echo -e "${YELLOW}Checking for existing module '${MODULE_NAME}'...${NC}"
+    if spacetime list | grep -q "${MODULE_NAME}"; then
+        echo -e "${YELLOW}Module '${MODULE_NAME}' exists, deleting...${NC}"
+        spacetime delete "${MODULE_NAME}" --force 2>/dev/null || true
+    fi
 fi
 
 # Publish the module
@@ -102,6 +112,13 @@ if [ $? -ne 0 ]; then
     exit 1
 fi
 
+# Bring existing data up to the new schema
+if [ "${UPGRADE}" = true ]; then
+    echo ""
+    echo -e "${YELLOW}Running schema migrations...${NC}"
+    spacetime call "${MODULE_NAME}" on_module_update
+fi
+
 echo ""
 echo -e "${GREEN}========================================${NC}"
 echo -e "${GREEN}  Module '${MODULE_NAME}' deployed!    ${NC}"
@@ -115,18 +132,56 @@ echo ""
 
 echo -e "${BLUE}Available Reducers:${NC}"
 echo "  - increment_counter(name: String, amount: i64)"
-echo "  - create_message(sender: String, content: String, channel: String)"
-echo "  - create_event(event_type: String, source: String, data: String)"
+echo "  - set_counter_owner(name: String, owner: Option<Identity>)"
+echo "  - set_counter(name: String, value: i64)"
+echo "  - reset_counter(name: String)"
+echo "  - compare_and_set_counter(name: String, expected_version: u64, new_value: i64)"
+echo "  - transfer(from: String, to: String, amount: i64)"
+echo "  - check_total_invariant(expected_total: i64)"
+echo "  - delete_counter(name: String)"
+echo "  - seed_counters(prefix: String, start: u64, count: u64)"
+echo "  - increment_sharded_counter(name: String, amount: i64, shard_hint: u32)"
+echo "  - materialize_sharded_counter(name: String)"
+echo "  - create_message(sender_name: Option<String>, content: String, channel: String, idempotency_key: Option<String>)"
+echo "  - create_message_with_metadata(sender_name: Option<String>, content: String, channel: String, priority: Option<u8>, tags: Vec<String>)"
+echo "  - create_event(event_type: String, source: String, data: EventPayload, idempotency_key: Option<String>)"
+echo "  - create_event_json(event_type: String, source: String, data: String)"
+echo "  - create_messages_batch(messages: Vec<NewMessage>)"
+echo "  - create_events_batch(events: Vec<NewEvent>)"
+echo "  - start_benchmark_session(name: String, config: String)"
+echo "  - end_benchmark_session(session_id: u64)"
+echo "  - delete_benchmark_session(session_id: u64, delete_data: bool)  # delete_data requires admin"
+echo "  - claim_events(worker: String, max: u32)"
+echo "  - ack_event(event_id: u64, worker: String)"
+echo "  - nack_event(event_id: u64, worker: String)"
+echo ""
+
+echo -e "${BLUE}Admin-only Reducers (callers must be in the admin table):${NC}"
+echo "  - add_admin(identity: Identity)"
+echo "  - remove_admin(identity: Identity)"
+echo "  - delete_counters_with_prefix(prefix: String)"
+echo "  - configure_batching(max_batch_size: u32)"
+echo "  - delete_old_messages(before_timestamp: Timestamp)"
+echo "  - delete_old_events(before_timestamp: Timestamp)"
+echo "  - configure_retention(message_max_age_secs: u64, message_max_rows: u64, event_max_age_secs: u64, event_max_rows: u64, interval_secs: u64)"
+echo "  - seed_data(counters: u32, messages_per_channel: u32, channels: u32, events: u32, rng_seed: Option<u64>)"
+echo "  - clear_data(tables: Vec<String>)"
+echo "  - configure_heartbeat(interval_ms: u64)"
+echo "  - configure_authorization(enforce_counter_ownership: bool)"
+echo "  - configure_rate_limit(per_sec: u32, burst: u32)"
+echo "  - configure_idempotency(ttl_secs: u64)"
+echo "  - on_module_update()"
 echo ""
 
-echo -e "${BLUE}Available Queries:${NC}"
-echo "  - get_counter(name: String) -> Option<Counter>"
-echo "  - get_messages(channel: String, limit: u32) -> Vec<Message>"
+echo -e "${BLUE}Available Queries (results readable through the my_counter_lookup / my_message_page views):${NC}"
+echo "  - get_counter(name: String, request_id: u64)"
+echo "  - get_messages(channel: String, limit: u32, before: Option<MessageCursor>, request_id: u64)"
+echo "  - get_messages_by_sender(sender: Identity, limit: u32, before: Option<MessageCursor>, request_id: u64)"
 echo ""
 
 echo -e "${YELLOW}To test the module:${NC}"
 echo "  spacetime call ${MODULE_NAME} increment_counter '{\"name\": \"test\", \"amount\": 1}'"
-echo "  spacetime call ${MODULE_NAME} create_message '{\"sender\": \"user1\", \"content\": \"Hello!\", \"channel\": \"general\"}'"
-echo "  spacetime call ${MODULE_NAME} get_counter '{\"name\": \"test\"}'"
-echo "  spacetime call ${MODULE_NAME} get_messages '{\"channel\": \"general\", \"limit\": 10}'"
+echo "  spacetime call ${MODULE_NAME} create_message '{\"sender_name\": {\"some\": \"user1\"}, \"content\": \"Hello!\", \"channel\": \"general\", \"idempotency_key\": {\"none\": []}}'"
+echo "  spacetime call ${MODULE_NAME} get_counter '{\"name\": \"test\", \"request_id\": 1}'"
+echo "  spacetime call ${MODULE_NAME} get_messages '{\"channel\": \"general\", \"limit\": 10, \"before\": {\"none\": []}, \"request_id\": 1}'"
 echo ""
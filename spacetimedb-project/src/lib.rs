@@ -1,14 +1,21 @@
 //! SpacetimeDB Benchmark Module
 //! This module provides the same functionality as the Convex benchmark for fair comparison
 
-use spacetimedb::{table, reducer, Table, Timestamp};
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use spacetimedb::log_stopwatch::LogStopwatch;
+use spacetimedb::{reducer, table, view, SpacetimeType, Table, Timestamp};
+use std::cmp::Reverse;
+use std::ops::Bound;
 
 // ============================================================================
 // Table Definitions
 // ============================================================================
 
 /// Counters table - stores named counter values
-/// Equivalent to Convex counters for benchmarking
+/// Equivalent to Convex counters for benchmarking. Columns added after the
+/// first release are appended with a default, so existing rows migrate in place
 #[table(name = counter, public)]
 pub struct Counter {
     /// Primary key - counter name
@@ -18,30 +25,329 @@ pub struct Counter {
     pub value: i64,
     /// Last update timestamp
     pub last_updated: Timestamp,
+    /// Optimistic concurrency version, bumped on every write
+    #[default(0u64)]
+    pub version: u64,
+    /// Benchmark session the counter was created in (0 = none)
+    /// Later writes don't change it, so deleting a session's data leaves
+    /// counters it only updated alone
+    #[index(btree)]
+    #[default(0u64)]
+    pub session_id: u64,
+    /// Identity allowed to write this counter (`None` = anyone)
+    #[default(None::<spacetimedb::Identity>)]
+    pub owner: Option<spacetimedb::Identity>,
 }
 
 /// Messages table - stores chat messages
-/// Equivalent to Convex messages for benchmarking
-#[table(name = message, public)]
+/// Equivalent to Convex messages for benchmarking, with matching indexes
+/// (by_channel, by_sender, by_timestamp, by_channel_timestamp). Index scans
+/// only run in ascending order, so the paging indexes use `newest_first`.
+/// Replaces the original `message` table, whose rows `on_module_update` copies here
+#[table(
+    name = message_v2,
+    public,
+    index(name = channel_newest_first, btree(columns = [channel, newest_first])),
+    index(name = sender_newest_first, btree(columns = [sender, newest_first]))
+)]
 pub struct Message {
     /// Auto-increment primary key
     #[primary_key]
     #[auto_inc]
     pub id: u64,
-    /// Message sender
-    pub sender: String,
+    /// Identity that posted the message
+    #[index(btree)]
+    pub sender: spacetimedb::Identity,
+    /// Display name chosen by the sender, if any
+    pub sender_name: Option<String>,
     /// Message content
     pub content: String,
     /// Channel name
+    #[index(btree)]
     pub channel: String,
+    /// Gap-free sequence number within the channel, starting at 1
+    pub seq: u64,
+    /// Optional priority (Convex `metadata.priority`)
+    pub priority: Option<u8>,
+    /// Tags (Convex `metadata.tags`), empty when not set
+    pub tags: Vec<String>,
     /// Message timestamp
     pub timestamp: Timestamp,
+    /// `timestamp` in microseconds since the Unix epoch, indexed for range
+    /// scans (`Timestamp` itself can't be range-filtered)
+    #[index(btree)]
+    pub timestamp_micros: i64,
+    /// Negated timestamp micros, so ascending index order is newest first
+    pub newest_first: i64,
+    /// Benchmark session the message was written in (0 = none)
+    #[index(btree)]
+    pub session_id: u64,
+}
+
+/// Channels table - registry of message channels
+/// Created on first message; tracks the per-channel sequence counter
+#[table(name = channel, public)]
+pub struct Channel {
+    /// Primary key - channel name
+    #[primary_key]
+    pub name: String,
+    /// When the first message was posted
+    pub created_at: Timestamp,
+    /// Number of messages ever posted to the channel
+    /// A lifetime count: deleting messages (retention, `clear_data`, session
+    /// deletes) doesn't lower it, so it tracks `last_seq`
+    pub message_count: u64,
+    /// Sequence number of the latest message
+    pub last_seq: u64,
+}
+
+/// Event payload - typed equivalent of Convex `events.data` plus `tags`
+/// Also read from the legacy JSON strings accepted by `create_event_json`
+#[derive(SpacetimeType, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct EventPayload {
+    /// Measured value
+    pub value: f64,
+    /// Optional unit for `value`
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Tags, empty when not set
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 /// Events table - stores event log entries
-/// Equivalent to Convex events for benchmarking
-#[table(name = event, public)]
+/// Equivalent to Convex events for benchmarking. Replaces the original `event`
+/// table, whose rows `on_module_update` copies here. The `claimable` index
+/// finds unprocessed events whose lease has lapsed without scanning live leases
+#[table(
+    name = event_v2,
+    public,
+    index(name = claimable, btree(columns = [processed, lease_expires_micros]))
+)]
 pub struct Event {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Event type
+    pub event_type: String,
+    /// Event source
+    pub source: String,
+    /// Typed event payload
+    pub data: EventPayload,
+    /// Event timestamp
+    pub timestamp: Timestamp,
+    /// `timestamp` in microseconds since the Unix epoch, indexed for range scans
+    #[index(btree)]
+    pub timestamp_micros: i64,
+    /// Set once a worker has acknowledged the event
+    pub processed: bool,
+    /// Worker holding the current lease, if any
+    pub claimed_by: Option<String>,
+    /// Identity that claimed the current lease; only it may ack or nack
+    pub claimed_by_identity: Option<spacetimedb::Identity>,
+    /// When the current lease lapses and the event becomes claimable again
+    pub lease_expires_at: Option<Timestamp>,
+    /// `lease_expires_at` in microseconds since the Unix epoch, 0 when unleased
+    pub lease_expires_micros: i64,
+    /// Number of times the event has been handed to a worker
+    pub attempts: u32,
+    /// Benchmark session the event was written in (0 = none)
+    #[index(btree)]
+    pub session_id: u64,
+}
+
+/// Dead-letter events - events that exhausted their delivery attempts
+/// Moved here from `event_v2` by `claim_events` so they stop being redelivered
+#[table(name = dead_letter_event, public)]
+pub struct DeadLetterEvent {
+    /// Primary key - id the event had in the `event_v2` table
+    #[primary_key]
+    pub event_id: u64,
+    /// Event type
+    pub event_type: String,
+    /// Event source
+    pub source: String,
+    /// Typed event payload
+    pub data: EventPayload,
+    /// Original event timestamp
+    pub timestamp: Timestamp,
+    /// Delivery attempts made before giving up
+    pub attempts: u32,
+    /// Worker that held the last lease
+    pub last_worker: Option<String>,
+    /// When the event was dead-lettered
+    pub dead_lettered_at: Timestamp,
+}
+
+/// Counter history table - one audit row per counter write
+/// Only present when built with the `counter-history` feature
+#[cfg(feature = "counter-history")]
+#[table(name = counter_history, public)]
+pub struct CounterHistory {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Name of the counter that was written
+    #[index(btree)]
+    pub counter_name: String,
+    /// Change applied by this write
+    pub delta: i64,
+    /// Counter value after the write
+    pub value: i64,
+    /// Identity of the caller
+    pub caller: spacetimedb::Identity,
+    /// Write timestamp
+    pub timestamp: Timestamp,
+}
+
+/// Sharded counters table - one row per (counter name, shard)
+/// Spreads writes to a hot counter across several rows to relieve contention
+#[table(
+    name = sharded_counter,
+    public,
+    index(name = name_shard, btree(columns = [name, shard]))
+)]
+pub struct ShardedCounter {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Logical counter name
+    pub name: String,
+    /// Shard index in `0..SHARDS_PER_COUNTER`
+    pub shard: u32,
+    /// Partial value held by this shard
+    pub value: i64,
+    /// Last update timestamp
+    pub last_updated: Timestamp,
+}
+
+/// Sharded counter summaries - aggregate of all shards for a counter
+/// Written by `materialize_sharded_counter`, not on every increment
+#[table(name = sharded_counter_summary, public)]
+pub struct ShardedCounterSummary {
+    /// Primary key - counter name
+    #[primary_key]
+    pub name: String,
+    /// Sum of all shard values
+    pub value: i64,
+    /// Number of shards that had been written to
+    pub shard_count: u32,
+    /// When the aggregate was computed
+    pub materialized_at: Timestamp,
+}
+
+/// Benchmark sessions - one row per benchmark run
+/// At most one session is active at a time; counters, messages and events
+/// created while it is active carry its id in `session_id`
+#[table(name = benchmark_session, public)]
+pub struct BenchmarkSession {
+    /// Auto-increment primary key (starts at 1, so 0 can mean "no session")
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Human-readable run name
+    pub name: String,
+    /// Run configuration (JSON string)
+    pub config: String,
+    /// When the session started
+    pub started_at: Timestamp,
+    /// When the session ended, `None` while active
+    pub ended_at: Option<Timestamp>,
+    /// Identity that started the session
+    pub created_by: spacetimedb::Identity,
+    /// Whether writes are currently attributed to this session
+    #[index(btree)]
+    pub active: bool,
+}
+
+/// Reducer latency histograms - execution time per (reducer, session)
+/// Filled by the `timed_*` procedures; only present when built with the
+/// `reducer-latency` feature. Buckets are powers of two: `buckets[0]` counts
+/// calls under 1 µs and `buckets[i]` counts calls in `[2^(i-1), 2^i)` µs. Only
+/// successful calls are recorded
+#[cfg(feature = "reducer-latency")]
+#[table(
+    name = reducer_latency,
+    public,
+    index(name = reducer_session, btree(columns = [reducer, session_id]))
+)]
+pub struct ReducerLatency {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Reducer name
+    pub reducer: String,
+    /// Benchmark session the calls were made in (0 = none)
+    pub session_id: u64,
+    /// Number of calls recorded
+    pub calls: u64,
+    /// Sum of durations in microseconds
+    pub total_micros: u64,
+    /// Longest duration in microseconds
+    pub max_micros: u64,
+    /// Histogram of durations, `LATENCY_BUCKETS` entries
+    pub buckets: Vec<u64>,
+    /// Start time of the first recorded call
+    pub first_call_at: Timestamp,
+    /// Start time of the latest recorded call
+    pub last_call_at: Timestamp,
+}
+
+/// Schema version - singleton row (id 0) recording the last applied migration
+#[table(name = schema_version, public)]
+pub struct SchemaVersion {
+    /// Primary key - always `SCHEMA_VERSION_ID`
+    #[primary_key]
+    pub id: u32,
+    /// Version of the last migration applied
+    pub version: u32,
+    /// When the version last changed
+    pub updated_at: Timestamp,
+}
+
+/// Migration log - one row per migration step applied
+#[table(name = migration_log, public)]
+pub struct MigrationLog {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Version the step migrates to
+    pub version: u32,
+    /// Step name
+    pub name: String,
+    /// Rows the step wrote
+    pub rows_affected: u64,
+    /// When the step ran
+    pub applied_at: Timestamp,
+}
+
+/// Legacy messages - the original `message` table, kept so databases
+/// published before `message_v2` still upgrade. Empty once migrated
+#[table(name = message, public)]
+pub struct LegacyMessage {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Caller-supplied sender name
+    pub sender: String,
+    /// Message content
+    pub content: String,
+    /// Channel name
+    pub channel: String,
+    /// Message timestamp
+    pub timestamp: Timestamp,
+}
+
+/// Legacy events - the original `event` table, kept so databases published
+/// before `event_v2` still upgrade. Empty once migrated
+#[table(name = event, public)]
+pub struct LegacyEvent {
     /// Auto-increment primary key
     #[primary_key]
     #[auto_inc]
@@ -56,90 +362,2958 @@ pub struct Event {
     pub timestamp: Timestamp,
 }
 
+/// Connections - one row per client connection, kept after disconnect
+/// Maintained by the `client_connected`/`client_disconnected` lifecycle reducers
+#[table(name = connection, public)]
+pub struct Connection {
+    /// Primary key - connection id assigned by the host
+    #[primary_key]
+    pub connection_id: spacetimedb::ConnectionId,
+    /// Identity of the connected client
+    #[index(btree)]
+    pub identity: spacetimedb::Identity,
+    /// When the client connected
+    pub connected_at: Timestamp,
+    /// When the client disconnected, `None` while connected
+    pub disconnected_at: Option<Timestamp>,
+}
+
+/// Connection stats - singleton row (id 0) of aggregate connection counts
+/// Reconcile against k6's `spacetime_ws_connections_total` and
+/// `spacetime_connection_drops_total`
+#[table(name = connection_stats, public)]
+pub struct ConnectionStats {
+    /// Primary key - always `CONNECTION_STATS_ID`
+    #[primary_key]
+    pub id: u32,
+    /// Clients currently connected
+    pub current_connections: u64,
+    /// Most clients connected at once
+    pub peak_connections: u64,
+    /// Connections opened since the module was published
+    pub total_connections: u64,
+    /// Connections closed since the module was published
+    pub total_disconnections: u64,
+    /// When the stats last changed
+    pub updated_at: Timestamp,
+}
+
+/// Admins - identities allowed to call destructive and configuration reducers
+/// Seeded with the publishing identity by `init`
+#[table(name = admin, public)]
+pub struct Admin {
+    /// Primary key - admin identity
+    #[primary_key]
+    pub identity: spacetimedb::Identity,
+    /// Admin that granted the role (the identity itself for the first admin)
+    pub added_by: spacetimedb::Identity,
+    /// When the role was granted
+    pub added_at: Timestamp,
+}
+
+/// Rate limit buckets - token bucket per (identity, reducer)
+/// Refilled lazily from `ctx.timestamp` whenever the identity calls the reducer
+#[table(
+    name = rate_limit_bucket,
+    public,
+    index(name = identity_reducer, btree(columns = [identity, reducer]))
+)]
+pub struct RateLimitBucket {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Calling identity
+    pub identity: spacetimedb::Identity,
+    /// Rate-limited reducer name
+    pub reducer: String,
+    /// Tokens left as of `refilled_at`; each call spends one
+    pub tokens: f64,
+    /// When `tokens` was last brought up to date
+    pub refilled_at: Timestamp,
+}
+
+/// Idempotency records - keys already applied by `create_message`/`create_event`
+/// A key is remembered until `expires_at_micros`, so client retries within the
+/// TTL don't insert the row twice. Keys are scoped to the reducer, not the
+/// caller: an unauthenticated HTTP retry arrives under a new identity
+#[table(name = idempotency_record, public)]
+pub struct IdempotencyRecord {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// `<reducer>/<key>`, unique so a key can only be applied once per reducer
+    #[unique]
+    pub scoped_key: String,
+    /// Identity that made the call
+    pub caller: spacetimedb::Identity,
+    /// Id of the message or event the key inserted
+    pub row_id: u64,
+    /// When the key may be reused, in microseconds since the Unix epoch
+    #[index(btree)]
+    pub expires_at_micros: i64,
+}
+
+/// Module settings - singleton row (id 0) of runtime configuration
+/// Missing until first configured; `settings()` falls back to defaults
+#[table(name = settings, public)]
+pub struct Settings {
+    /// Primary key - always `SETTINGS_ID`
+    #[primary_key]
+    pub id: u32,
+    /// Most rows a single batch insert reducer may write
+    pub max_batch_size: u32,
+    /// Messages older than this are deleted by `run_retention` (0 = no limit)
+    pub message_max_age_secs: u64,
+    /// Messages beyond this count are deleted oldest first (0 = no limit)
+    pub message_max_rows: u64,
+    /// Events older than this are deleted by `run_retention` (0 = no limit)
+    pub event_max_age_secs: u64,
+    /// Events beyond this count are deleted oldest first (0 = no limit)
+    pub event_max_rows: u64,
+    /// How often `run_retention` fires
+    pub retention_interval_secs: u64,
+    /// How often the `heartbeat` reducer fires (0 = disabled)
+    pub heartbeat_interval_ms: u64,
+    /// Whether writes to an owned counter are rejected for non-owners
+    pub enforce_counter_ownership: bool,
+    /// Calls per second each identity may make to a rate-limited reducer (0 = unlimited)
+    pub rate_limit_per_sec: u32,
+    /// Most calls an identity may burst before being limited
+    pub rate_limit_burst: u32,
+    /// How long an idempotency key is remembered
+    pub idempotency_ttl_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            id: SETTINGS_ID,
+            max_batch_size: 1000,
+            message_max_age_secs: 0,
+            message_max_rows: 0,
+            event_max_age_secs: 0,
+            event_max_rows: 0,
+            retention_interval_secs: 60,
+            heartbeat_interval_ms: 1000,
+            enforce_counter_ownership: true,
+            rate_limit_per_sec: 0,
+            rate_limit_burst: 10,
+            idempotency_ttl_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+/// Heartbeat schedule - next pending `heartbeat` firing
+/// Each firing is a one-shot at an exact time, so the reducer knows when it
+/// was supposed to run and can measure scheduler jitter
+#[table(name = heartbeat_schedule, scheduled(heartbeat))]
+pub struct HeartbeatSchedule {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub scheduled_id: u64,
+    /// When the heartbeat should fire
+    pub scheduled_at: spacetimedb::ScheduleAt,
+}
+
+/// Heartbeat ticks - one row per `heartbeat` firing
+/// Server-side measure of scheduler jitter, independent of client timing.
+/// Only the latest `HEARTBEAT_TICKS_KEPT` are kept
+#[table(name = heartbeat_tick, public)]
+pub struct HeartbeatTick {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// When the heartbeat was scheduled to fire
+    pub scheduled_for: Timestamp,
+    /// When the heartbeat actually fired
+    pub fired_at: Timestamp,
+    /// `fired_at` in microseconds since the Unix epoch, indexed for trimming
+    #[index(btree)]
+    pub fired_at_micros: i64,
+    /// `fired_at - scheduled_for` in microseconds
+    pub jitter_micros: i64,
+}
+
+/// Retention schedule - drives the periodic `run_retention` reducer
+/// Rewritten by `configure_retention`; empty while retention is disabled
+#[table(name = retention_schedule, scheduled(run_retention))]
+pub struct RetentionSchedule {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub scheduled_id: u64,
+    /// When (and how often) the retention reducer fires
+    pub scheduled_at: spacetimedb::ScheduleAt,
+}
+
+/// Message fields supplied by the caller of `create_messages_batch`
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct NewMessage {
+    /// Display name of the sender, if any
+    pub sender_name: Option<String>,
+    /// Message content
+    pub content: String,
+    /// Channel name
+    pub channel: String,
+    /// Optional priority
+    pub priority: Option<u8>,
+    /// Tags, may be empty
+    pub tags: Vec<String>,
+}
+
+/// Event fields supplied by the caller of `create_events_batch`
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct NewEvent {
+    /// Event type
+    pub event_type: String,
+    /// Event source
+    pub source: String,
+    /// Typed event payload
+    pub data: EventPayload,
+}
+
+/// Position of a message in newest-first order
+/// Pages are ordered by timestamp, with ties broken by id
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub struct MessageCursor {
+    /// Timestamp of the last message on the previous page
+    pub timestamp: Timestamp,
+    /// Id of the last message on the previous page
+    pub id: u64,
+}
+
+/// Message pages - latest message query result per caller and request id
+/// Reducers can't return rows, so read reducers write their result here.
+/// Private; clients read their own pages through the `my_message_page` view.
+/// Kept for `QUERY_RESULT_TTL_SECS` after the last query with its request id
+#[table(
+    name = message_page,
+    index(name = caller_request, btree(columns = [caller, request_id]))
+)]
+pub struct MessagePage {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Identity that issued the query
+    pub caller: spacetimedb::Identity,
+    /// Client-chosen request id, so clients sharing an identity (e.g. k6 VUs)
+    /// don't overwrite each other's pages
+    pub request_id: u64,
+    /// Messages on this page, newest first
+    pub messages: Vec<Message>,
+    /// Pass as `before` to fetch the next page; `None` on the last page
+    pub next_cursor: Option<MessageCursor>,
+    /// When the query ran
+    pub fetched_at: Timestamp,
+    /// When the page is deleted, in microseconds since the Unix epoch
+    #[index(btree)]
+    pub expires_at_micros: i64,
+}
+
+/// Counter lookups - latest `get_counter` result per caller and request id
+/// Private; clients read their own lookups through the `my_counter_lookup` view.
+/// Expire like `MessagePage`
+#[table(
+    name = counter_lookup,
+    index(name = caller_request, btree(columns = [caller, request_id]))
+)]
+pub struct CounterLookup {
+    /// Auto-increment primary key
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Identity that issued the query
+    pub caller: spacetimedb::Identity,
+    /// Client-chosen request id, as for `MessagePage`
+    pub request_id: u64,
+    /// Counter name that was looked up
+    pub name: String,
+    /// The counter, or `None` if it doesn't exist
+    pub counter: Option<Counter>,
+    /// When the query ran
+    pub fetched_at: Timestamp,
+    /// When the lookup is deleted, in microseconds since the Unix epoch
+    #[index(btree)]
+    pub expires_at_micros: i64,
+}
+
+// ============================================================================
+// Call Stats
+// ============================================================================
+
+// Every workload reducer opens a `LogStopwatch` span, so the host writes its
+// execution time to the module log. A reducer can't read a clock itself, so
+// durations only reach `reducer_latency` through the `timed_*` procedures,
+// which run the reducer in its own transaction and read the host timestamps
+// of that transaction and the next one
+
+/// Number of histogram buckets; the last one also holds anything slower
+#[cfg(feature = "reducer-latency")]
+const LATENCY_BUCKETS: usize = 32;
+
+/// Histogram bucket for a duration in microseconds
+#[cfg(feature = "reducer-latency")]
+fn latency_bucket(micros: u64) -> usize {
+    ((u64::BITS - micros.leading_zeros()) as usize).min(LATENCY_BUCKETS - 1)
+}
+
+/// Add one call to the (reducer, current session) histogram row
+#[cfg(feature = "reducer-latency")]
+fn record_latency(
+    ctx: &spacetimedb::ReducerContext,
+    reducer: &str,
+    started: Timestamp,
+    micros: u64,
+) {
+    let session_id = current_session(ctx);
+    let latencies = ctx.db.reducer_latency();
+    let mut row = latencies
+        .reducer_session()
+        .filter((reducer, session_id))
+        .next()
+        .unwrap_or_else(|| ReducerLatency {
+            id: 0, // Will be auto-generated
+            reducer: reducer.to_string(),
+            session_id,
+            calls: 0,
+            total_micros: 0,
+            max_micros: 0,
+            buckets: vec![0; LATENCY_BUCKETS],
+            first_call_at: started,
+            last_call_at: started,
+        });
+
+    row.calls += 1;
+    row.total_micros += micros;
+    row.max_micros = row.max_micros.max(micros);
+    row.buckets[latency_bucket(micros)] += 1;
+    row.last_call_at = started;
+
+    if row.id == 0 {
+        latencies.insert(row);
+    } else {
+        latencies.id().update(row);
+    }
+}
+
+/// Return types of the reducers `timed` can run
+#[cfg(feature = "reducer-latency")]
+trait ReducerOutcome {
+    fn into_result(self) -> Result<(), String>;
+}
+
+#[cfg(feature = "reducer-latency")]
+impl ReducerOutcome for () {
+    fn into_result(self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "reducer-latency")]
+impl ReducerOutcome for Result<(), String> {
+    fn into_result(self) -> Result<(), String> {
+        self
+    }
+}
+
+/// Run a reducer body in its own transaction and record its duration
+/// The duration runs from the start of that transaction to the start of the
+/// one recording it, so it covers execution and commit. A failed call rolls
+/// back and records nothing
+#[cfg(feature = "reducer-latency")]
+fn timed<R: ReducerOutcome>(
+    ctx: &mut spacetimedb::ProcedureContext,
+    reducer: &str,
+    body: impl Fn(&spacetimedb::ReducerContext) -> R,
+) -> Result<(), String> {
+    let started = ctx.try_with_tx(|tx| body(tx).into_result().map(|()| tx.timestamp))?;
+    ctx.with_tx(|tx| {
+        let micros = tx
+            .timestamp
+            .to_micros_since_unix_epoch()
+            .saturating_sub(started.to_micros_since_unix_epoch());
+        record_latency(tx, reducer, started, micros.max(0) as u64);
+    });
+    Ok(())
+}
+
+/// Declare a `timed_<reducer>` procedure taking the reducer's arguments
+macro_rules! timed_procedures {
+    ($($procedure:ident => $reducer:ident($($arg:ident: $ty:ty),*);)*) => {$(
+        #[doc = concat!("`", stringify!($reducer), "`, recording its duration into `reducer_latency`")]
+        #[cfg(feature = "reducer-latency")]
+        #[spacetimedb::procedure]
+        pub fn $procedure(
+            ctx: &mut spacetimedb::ProcedureContext,
+            $($arg: $ty),*
+        ) -> Result<(), String> {
+            timed(ctx, stringify!($reducer), |tx| $reducer(tx, $($arg.clone()),*))
+        }
+    )*};
+}
+
+timed_procedures! {
+    timed_increment_counter => increment_counter(name: String, amount: i64);
+    timed_set_counter => set_counter(name: String, value: i64);
+    timed_compare_and_set_counter => compare_and_set_counter(name: String, expected_version: u64, new_value: i64);
+    timed_transfer => transfer(from: String, to: String, amount: i64);
+    timed_increment_sharded_counter => increment_sharded_counter(name: String, amount: i64, shard_hint: u32);
+    timed_create_message => create_message(sender_name: Option<String>, content: String, channel: String, idempotency_key: Option<String>);
+    timed_create_event => create_event(event_type: String, source: String, data: EventPayload, idempotency_key: Option<String>);
+    timed_create_messages_batch => create_messages_batch(messages: Vec<NewMessage>);
+    timed_create_events_batch => create_events_batch(events: Vec<NewEvent>);
+    timed_claim_events => claim_events(worker: String, max: u32);
+    timed_ack_event => ack_event(event_id: u64, worker: String);
+    timed_get_messages => get_messages(channel: String, limit: u32, before: Option<MessageCursor>, request_id: u64);
+    timed_get_messages_by_sender => get_messages_by_sender(sender: spacetimedb::Identity, limit: u32, before: Option<MessageCursor>, request_id: u64);
+    timed_get_counter => get_counter(name: String, request_id: u64);
+}
+
 // ============================================================================
 // Reducers (Mutations)
 // ============================================================================
 
+/// Append an audit row for a counter write
+/// Compiled out unless the `counter-history` feature is enabled, so the
+/// default build pays no write amplification
+fn record_counter_history(ctx: &spacetimedb::ReducerContext, counter: &Counter, delta: i64) {
+    #[cfg(feature = "counter-history")]
+    ctx.db.counter_history().insert(CounterHistory {
+        id: 0, // Will be auto-generated
+        counter_name: counter.name.clone(),
+        delta,
+        value: counter.value,
+        caller: ctx.sender,
+        timestamp: ctx.timestamp,
+    });
+
+    #[cfg(not(feature = "counter-history"))]
+    let _ = (ctx, counter, delta);
+}
+
 /// Increment a counter by the specified amount
-/// Creates the counter if it doesn't exist
+/// Creates the counter (unowned) if it doesn't exist; fails rather than overflow
 #[reducer]
-pub fn increment_counter(ctx: &spacetimedb::ReducerContext, name: String, amount: i64) {
+pub fn increment_counter(
+    ctx: &spacetimedb::ReducerContext,
+    name: String,
+    amount: i64,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("increment_counter");
+    take_rate_limit_token(ctx, "increment_counter")?;
     let timestamp = ctx.timestamp;
+    let session_id = current_session(ctx);
     let counters = ctx.db.counter();
 
-    // Check if counter exists by trying to find it
-    let existing = counters.iter().find(|c| c.name == name);
-
-    if let Some(counter) = existing {
-        // Delete old and insert updated (SpacetimeDB pattern for updates)
-        let new_value = counter.value + amount;
-        counters.delete(counter);
-        counters.insert(Counter {
-            name,
-            value: new_value,
+    // Look up through the primary key index so cost doesn't grow with table size
+    let updated = if let Some(counter) = counters.name().find(&name) {
+        check_counter_write(ctx, &counter)?;
+        let value = counter
+            .value
+            .checked_add(amount)
+            .ok_or_else(|| format!("Counter '{name}' would overflow"))?;
+        counters.name().update(Counter {
+            value,
+            version: counter.version + 1,
             last_updated: timestamp,
-        });
+            ..counter
+        })
     } else {
         // Create new counter
         counters.insert(Counter {
             name,
             value: amount,
+            version: 0,
             last_updated: timestamp,
-        });
+            session_id,
+            owner: None,
+        })
+    };
+
+    record_counter_history(ctx, &updated, amount);
+    Ok(())
+}
+
+/// Reject the write if `counter` is owned by someone other than the caller
+/// Always passes while `enforce_counter_ownership` is off
+fn check_counter_write(ctx: &spacetimedb::ReducerContext, counter: &Counter) -> Result<(), String> {
+    match counter.owner {
+        Some(owner) if owner != ctx.sender && settings(ctx).enforce_counter_ownership => {
+            Err(format!("Counter '{}' is owned by {}", counter.name, owner))
+        }
+        _ => Ok(()),
     }
 }
 
-/// Create a new message in the specified channel
+/// Claim, hand over or release ownership of a counter
+/// Only the current owner may change it; an unowned counter can be claimed by anyone
 #[reducer]
-pub fn create_message(
+pub fn set_counter_owner(
     ctx: &spacetimedb::ReducerContext,
-    sender: String,
-    content: String,
-    channel: String,
-) {
-    let timestamp = ctx.timestamp;
+    name: String,
+    owner: Option<spacetimedb::Identity>,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("set_counter_owner");
+    let counters = ctx.db.counter();
+    let counter = counters
+        .name()
+        .find(&name)
+        .ok_or_else(|| format!("Counter '{name}' not found"))?;
+    check_counter_write(ctx, &counter)?;
 
-    ctx.db.message().insert(Message {
-        id: 0, // Will be auto-generated
-        sender,
-        content,
-        channel,
-        timestamp,
+    counters.name().update(Counter {
+        owner,
+        version: counter.version + 1,
+        last_updated: ctx.timestamp,
+        ..counter
     });
+    Ok(())
 }
 
-/// Create a new event log entry
+/// Set a counter to an explicit value
+/// Fails if the counter doesn't exist
 #[reducer]
-pub fn create_event(
+pub fn set_counter(
     ctx: &spacetimedb::ReducerContext,
-    event_type: String,
-    source: String,
-    data: String,
-) {
-    let timestamp = ctx.timestamp;
+    name: String,
+    value: i64,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("set_counter");
+    write_counter_value(ctx, name, value)
+}
 
-    ctx.db.event().insert(Event {
-        id: 0, // Will be auto-generated
-        event_type,
-        source,
-        data,
-        timestamp,
+/// Overwrite an existing counter's value
+fn write_counter_value(
+    ctx: &spacetimedb::ReducerContext,
+    name: String,
+    value: i64,
+) -> Result<(), String> {
+    let counters = ctx.db.counter();
+    let counter = counters
+        .name()
+        .find(&name)
+        .ok_or_else(|| format!("Counter '{name}' not found"))?;
+    check_counter_write(ctx, &counter)?;
+
+    let delta = value - counter.value;
+    let updated = counters.name().update(Counter {
+        value,
+        version: counter.version + 1,
+        last_updated: ctx.timestamp,
+        ..counter
     });
+    record_counter_history(ctx, &updated, delta);
+    Ok(())
 }
 
-// ============================================================================
-// Initialization
-// ============================================================================
-
-/// Called when the module is first published/initialized
+/// Set a counter only if its version still matches `expected_version`
+/// Fails on a version mismatch so callers can count conflicts and retry
 #[reducer]
-pub fn init(_ctx: &spacetimedb::ReducerContext) {
-    // Initialize with some default data if needed
-    // This runs once when the module is published
+pub fn compare_and_set_counter(
+    ctx: &spacetimedb::ReducerContext,
+    name: String,
+    expected_version: u64,
+    new_value: i64,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("compare_and_set_counter");
+    let counters = ctx.db.counter();
+    let counter = counters
+        .name()
+        .find(&name)
+        .ok_or_else(|| format!("Counter '{name}' not found"))?;
+    check_counter_write(ctx, &counter)?;
+
+    if counter.version != expected_version {
+        return Err(format!(
+            "Version conflict on counter '{}': expected {}, found {}",
+            name, expected_version, counter.version
+        ));
+    }
+
+    let delta = new_value - counter.value;
+    let updated = counters.name().update(Counter {
+        value: new_value,
+        version: counter.version + 1,
+        last_updated: ctx.timestamp,
+        ..counter
+    });
+    record_counter_history(ctx, &updated, delta);
+    Ok(())
 }
 
-/// Called when the module is updated to a new version
+/// Move `amount` from one counter to another in a single transaction
+/// Fails without writing anything if either counter is missing or `from` would go negative
 #[reducer]
-pub fn on_module_update(_ctx: &spacetimedb::ReducerContext) {
-    // Handle any migration logic here
+pub fn transfer(
+    ctx: &spacetimedb::ReducerContext,
+    from: String,
+    to: String,
+    amount: i64,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("transfer");
+    if amount <= 0 {
+        return Err(format!("Transfer amount must be positive, got {amount}"));
+    }
+    if from == to {
+        return Err(format!("Cannot transfer from counter '{from}' to itself"));
+    }
+
+    let counters = ctx.db.counter();
+    let source = counters
+        .name()
+        .find(&from)
+        .ok_or_else(|| format!("Counter '{from}' not found"))?;
+    let target = counters
+        .name()
+        .find(&to)
+        .ok_or_else(|| format!("Counter '{to}' not found"))?;
+    check_counter_write(ctx, &source)?;
+    check_counter_write(ctx, &target)?;
+
+    if source.value < amount {
+        return Err(format!(
+            "Insufficient balance on counter '{}': has {}, needs {}",
+            from, source.value, amount
+        ));
+    }
+    let credited = target
+        .value
+        .checked_add(amount)
+        .ok_or_else(|| format!("Counter '{to}' would overflow"))?;
+
+    let debited = counters.name().update(Counter {
+        value: source.value - amount,
+        version: source.version + 1,
+        last_updated: ctx.timestamp,
+        ..source
+    });
+    let credited = counters.name().update(Counter {
+        value: credited,
+        version: target.version + 1,
+        last_updated: ctx.timestamp,
+        ..target
+    });
+    record_counter_history(ctx, &debited, -amount);
+    record_counter_history(ctx, &credited, amount);
+    Ok(())
+}
+
+/// Verify that the sum over all counters equals `expected_total`
+/// Run after a transfer load test to assert no value was created or lost
+#[reducer]
+pub fn check_total_invariant(
+    ctx: &spacetimedb::ReducerContext,
+    expected_total: i64,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("check_total_invariant");
+    let (count, total) = ctx
+        .db
+        .counter()
+        .iter()
+        .fold((0u64, 0i128), |(count, total), c| {
+            (count + 1, total + c.value as i128)
+        });
+
+    if total != expected_total as i128 {
+        return Err(format!(
+            "Total invariant violated: expected {expected_total}, found {total} across {count} counters"
+        ));
+    }
+
+    log::info!("Total invariant holds: {} across {} counters", total, count);
+    Ok(())
+}
+
+/// Reset a counter back to zero
+/// Fails if the counter doesn't exist
+#[reducer]
+pub fn reset_counter(ctx: &spacetimedb::ReducerContext, name: String) -> Result<(), String> {
+    let _span = LogStopwatch::new("reset_counter");
+    write_counter_value(ctx, name, 0)
+}
+
+/// Delete a counter
+/// Fails if the counter doesn't exist
+#[reducer]
+pub fn delete_counter(ctx: &spacetimedb::ReducerContext, name: String) -> Result<(), String> {
+    let _span = LogStopwatch::new("delete_counter");
+    let counters = ctx.db.counter();
+    let counter = counters
+        .name()
+        .find(&name)
+        .ok_or_else(|| format!("Counter '{name}' not found"))?;
+    check_counter_write(ctx, &counter)?;
+
+    counters.name().delete(&name);
+    Ok(())
+}
+
+/// Delete every counter whose name starts with `prefix`
+/// Fails without deleting anything if no counter matches or any match
+/// is owned by someone other than the caller
+#[reducer]
+pub fn delete_counters_with_prefix(
+    ctx: &spacetimedb::ReducerContext,
+    prefix: String,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("delete_counters_with_prefix");
+    require_admin(ctx, "delete_counters_with_prefix")?;
+    let counters = ctx.db.counter();
+
+    // Collect first: the table can't be modified while it's being iterated
+    let matches: Vec<Counter> = counters
+        .iter()
+        .filter(|c| c.name.starts_with(&prefix))
+        .collect();
+
+    if matches.is_empty() {
+        return Err(format!("No counters found with prefix '{prefix}'"));
+    }
+    for counter in &matches {
+        check_counter_write(ctx, counter)?;
+    }
+    let names: Vec<String> = matches.into_iter().map(|c| c.name).collect();
+
+    for name in &names {
+        counters.name().delete(name);
+    }
+
+    log::info!("Deleted {} counters with prefix '{}'", names.len(), prefix);
+    Ok(())
+}
+
+/// Most counters a single `seed_counters` call may insert
+const MAX_SEED_COUNTERS_PER_CALL: u64 = 10_000;
+
+/// Seed `count` zeroed counters named `{prefix}{index}`, starting at `start`
+/// Used by the counter scaling benchmark to grow the table in chunks of at
+/// most `MAX_SEED_COUNTERS_PER_CALL`; counters that already exist are left untouched
+#[reducer]
+pub fn seed_counters(
+    ctx: &spacetimedb::ReducerContext,
+    prefix: String,
+    start: u64,
+    count: u64,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("seed_counters");
+    if count > MAX_SEED_COUNTERS_PER_CALL {
+        return Err(format!(
+            "Cannot seed {count} counters in one call, the limit is {MAX_SEED_COUNTERS_PER_CALL}"
+        ));
+    }
+
+    let timestamp = ctx.timestamp;
+    let session_id = current_session(ctx);
+    let counters = ctx.db.counter();
+
+    for index in start..start.saturating_add(count) {
+        let name = format!("{prefix}{index}");
+        if counters.name().find(&name).is_none() {
+            counters.insert(Counter {
+                name,
+                value: 0,
+                version: 0,
+                last_updated: timestamp,
+                session_id,
+                owner: None,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Number of shards each sharded counter is striped across
+const SHARDS_PER_COUNTER: u32 = 16;
+
+/// Increment a sharded counter by the specified amount
+/// `shard_hint` (e.g. a client or VU id) picks the shard, so concurrent writers
+/// land on different rows; the shard row is created if it doesn't exist.
+/// Fails rather than overflow the shard
+#[reducer]
+pub fn increment_sharded_counter(
+    ctx: &spacetimedb::ReducerContext,
+    name: String,
+    amount: i64,
+    shard_hint: u32,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("increment_sharded_counter");
+    let shard = shard_hint % SHARDS_PER_COUNTER;
+    let shards = ctx.db.sharded_counter();
+
+    let existing = shards.name_shard().filter((&name, shard)).next();
+    if let Some(row) = existing {
+        let value = row
+            .value
+            .checked_add(amount)
+            .ok_or_else(|| format!("Shard {shard} of counter '{name}' would overflow"))?;
+        shards.id().update(ShardedCounter {
+            value,
+            last_updated: ctx.timestamp,
+            ..row
+        });
+    } else {
+        shards.insert(ShardedCounter {
+            id: 0, // Will be auto-generated
+            name,
+            shard,
+            value: amount,
+            last_updated: ctx.timestamp,
+        });
+    }
+    Ok(())
+}
+
+/// Sum all shards of a sharded counter into its summary row
+/// Fails if the counter has no shards or the sum overflows
+#[reducer]
+pub fn materialize_sharded_counter(
+    ctx: &spacetimedb::ReducerContext,
+    name: String,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("materialize_sharded_counter");
+    let (shard_count, total) = ctx
+        .db
+        .sharded_counter()
+        .name_shard()
+        .filter(&name)
+        .fold((0u32, 0i128), |(count, total), row| {
+            (count + 1, total + row.value as i128)
+        });
+
+    if shard_count == 0 {
+        return Err(format!("Sharded counter '{name}' not found"));
+    }
+    let value = i64::try_from(total)
+        .map_err(|_| format!("Sharded counter '{name}' sums to {total}, which overflows"))?;
+
+    let summary = ShardedCounterSummary {
+        name,
+        value,
+        shard_count,
+        materialized_at: ctx.timestamp,
+    };
+    let summaries = ctx.db.sharded_counter_summary();
+    if summaries.name().find(&summary.name).is_some() {
+        summaries.name().update(summary);
+    } else {
+        summaries.insert(summary);
+    }
+    Ok(())
+}
+
+/// Reserve the next sequence number for a channel, registering it if new
+/// Runs in the caller's transaction, so sequence numbers are gap-free
+fn next_channel_seq(ctx: &spacetimedb::ReducerContext, name: &str) -> u64 {
+    let channels = ctx.db.channel();
+    let name = name.to_string();
+
+    if let Some(channel) = channels.name().find(&name) {
+        let seq = channel.last_seq + 1;
+        channels.name().update(Channel {
+            message_count: channel.message_count + 1,
+            last_seq: seq,
+            ..channel
+        });
+        seq
+    } else {
+        channels.insert(Channel {
+            name,
+            created_at: ctx.timestamp,
+            message_count: 1,
+            last_seq: 1,
+        });
+        1
+    }
+}
+
+/// Create a new message in the specified channel
+/// The sender is always the calling identity; `sender_name` is only an
+/// optional display name. A repeated `idempotency_key` inserts nothing
+#[reducer]
+pub fn create_message(
+    ctx: &spacetimedb::ReducerContext,
+    sender_name: Option<String>,
+    content: String,
+    channel: String,
+    idempotency_key: Option<String>,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("create_message");
+    // A retried call that already applied shouldn't spend a token
+    if is_duplicate_key(ctx, "create_message", idempotency_key.as_ref()) {
+        return Ok(());
+    }
+    take_rate_limit_token(ctx, "create_message")?;
+
+    let message = insert_message(ctx, sender_name, content, channel, None, Vec::new());
+    if let Some(key) = idempotency_key {
+        record_idempotency_key(ctx, key, "create_message", message.id);
+    }
+    Ok(())
+}
+
+/// Create a new message carrying Convex-style metadata
+/// Matches the payload of Convex `createMessage` with `metadata` set
+#[reducer]
+pub fn create_message_with_metadata(
+    ctx: &spacetimedb::ReducerContext,
+    sender_name: Option<String>,
+    content: String,
+    channel: String,
+    priority: Option<u8>,
+    tags: Vec<String>,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("create_message_with_metadata");
+    take_rate_limit_token(ctx, "create_message_with_metadata")?;
+    insert_message(ctx, sender_name, content, channel, priority, tags);
+    Ok(())
+}
+
+/// Insert a message from the caller with the next sequence number for its channel
+fn insert_message(
+    ctx: &spacetimedb::ReducerContext,
+    sender_name: Option<String>,
+    content: String,
+    channel: String,
+    priority: Option<u8>,
+    tags: Vec<String>,
+) -> Message {
+    let timestamp = ctx.timestamp;
+    let seq = next_channel_seq(ctx, &channel);
+
+    ctx.db.message_v2().insert(Message {
+        id: 0, // Will be auto-generated
+        sender: ctx.sender,
+        sender_name,
+        content,
+        channel,
+        seq,
+        priority,
+        tags,
+        timestamp,
+        timestamp_micros: timestamp.to_micros_since_unix_epoch(),
+        newest_first: newest_first_key(timestamp),
+        session_id: current_session(ctx),
+    })
+}
+
+/// Create a new event log entry
+/// A repeated `idempotency_key` inserts nothing
+#[reducer]
+pub fn create_event(
+    ctx: &spacetimedb::ReducerContext,
+    event_type: String,
+    source: String,
+    data: EventPayload,
+    idempotency_key: Option<String>,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("create_event");
+    // A retried call that already applied shouldn't spend a token
+    if is_duplicate_key(ctx, "create_event", idempotency_key.as_ref()) {
+        return Ok(());
+    }
+    take_rate_limit_token(ctx, "create_event")?;
+
+    let event = insert_event(ctx, event_type, source, data);
+    if let Some(key) = idempotency_key {
+        record_idempotency_key(ctx, key, "create_event", event.id);
+    }
+    Ok(())
+}
+
+/// Insert a new, unclaimed event
+fn insert_event(
+    ctx: &spacetimedb::ReducerContext,
+    event_type: String,
+    source: String,
+    data: EventPayload,
+) -> Event {
+    let timestamp = ctx.timestamp;
+
+    ctx.db.event_v2().insert(Event {
+        id: 0, // Will be auto-generated
+        event_type,
+        source,
+        data,
+        timestamp,
+        timestamp_micros: timestamp.to_micros_since_unix_epoch(),
+        processed: false,
+        claimed_by: None,
+        claimed_by_identity: None,
+        lease_expires_at: None,
+        lease_expires_micros: 0,
+        attempts: 0,
+        session_id: current_session(ctx),
+    })
+}
+
+/// Create a new event log entry from a raw JSON payload
+/// Backwards-compatible form of `create_event` for clients that still send
+/// `data` as a string. Like the original reducer it accepts any string; the
+/// JSON is converted once here, never on reads
+#[reducer]
+pub fn create_event_json(
+    ctx: &spacetimedb::ReducerContext,
+    event_type: String,
+    source: String,
+    data: String,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("create_event_json");
+    take_rate_limit_token(ctx, "create_event_json")?;
+    insert_event(ctx, event_type, source, legacy_event_payload(&data));
+    Ok(())
+}
+
+/// Convert a free-form JSON `data` string to an `EventPayload`
+/// JSON with a numeric `value` is read field by field; anything else is kept
+/// verbatim as the only tag, with a `value` of 0
+fn legacy_event_payload(data: &str) -> EventPayload {
+    serde_json::from_str(data).unwrap_or_else(|_| EventPayload {
+        value: 0.0,
+        unit: None,
+        tags: vec![data.to_string()],
+    })
+}
+
+// ============================================================================
+// Reducers (Batching)
+// ============================================================================
+
+/// Reject batches that are empty or larger than the configured maximum
+fn check_batch_size(ctx: &spacetimedb::ReducerContext, len: usize) -> Result<(), String> {
+    let max = settings(ctx).max_batch_size;
+    if len == 0 {
+        return Err("Batch is empty".to_string());
+    }
+    if len > max as usize {
+        return Err(format!(
+            "Batch of {len} rows exceeds max_batch_size of {max}"
+        ));
+    }
+    Ok(())
+}
+
+/// Insert a batch of messages in one transaction
+/// Either every message is inserted or none is. The whole batch spends one
+/// rate limit token
+#[reducer]
+pub fn create_messages_batch(
+    ctx: &spacetimedb::ReducerContext,
+    messages: Vec<NewMessage>,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("create_messages_batch");
+    check_batch_size(ctx, messages.len())?;
+    take_rate_limit_token(ctx, "create_messages_batch")?;
+
+    for m in messages {
+        insert_message(ctx, m.sender_name, m.content, m.channel, m.priority, m.tags);
+    }
+    Ok(())
+}
+
+/// Insert a batch of events in one transaction
+/// Either every event is inserted or none is, for one rate limit token
+#[reducer]
+pub fn create_events_batch(
+    ctx: &spacetimedb::ReducerContext,
+    events: Vec<NewEvent>,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("create_events_batch");
+    check_batch_size(ctx, events.len())?;
+    take_rate_limit_token(ctx, "create_events_batch")?;
+
+    for e in events {
+        insert_event(ctx, e.event_type, e.source, e.data);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Reducers (Event Queue)
+// ============================================================================
+
+/// How long a claimed event stays leased to a worker
+const EVENT_LEASE_MICROS: i64 = 30_000_000;
+
+/// Deliveries after which an unacknowledged event is dead-lettered
+const MAX_EVENT_ATTEMPTS: u32 = 5;
+
+/// Timestamp `micros` microseconds after `timestamp`, saturating at the
+/// representable range
+fn micros_after(timestamp: Timestamp, micros: i64) -> Timestamp {
+    Timestamp::from_micros_since_unix_epoch(
+        timestamp
+            .to_micros_since_unix_epoch()
+            .saturating_add(micros),
+    )
+}
+
+/// Lease up to `max` unprocessed events to `worker`
+/// Events whose lease has lapsed are redelivered; events that have already
+/// been delivered `MAX_EVENT_ATTEMPTS` times are moved to `dead_letter_event`
+/// and count toward `max`, so each call touches at most `max` events.
+/// Workers see their events by subscribing with `WHERE claimed_by = '<worker>'`
+#[reducer]
+pub fn claim_events(ctx: &spacetimedb::ReducerContext, worker: String, max: u32) {
+    let _span = LogStopwatch::new("claim_events");
+    let events = ctx.db.event_v2();
+    let now = ctx.timestamp;
+
+    // Unleased events sort first at 0, then lapsed leases; live leases lie
+    // past `now` and are never read. Collect first: the table can't be
+    // modified while it's being iterated
+    let claimable: Vec<Event> = events
+        .claimable()
+        .filter((false, ..=now.to_micros_since_unix_epoch()))
+        .take(max as usize)
+        .collect();
+
+    for event in claimable {
+        if event.attempts >= MAX_EVENT_ATTEMPTS {
+            events.id().delete(event.id);
+            ctx.db.dead_letter_event().insert(DeadLetterEvent {
+                event_id: event.id,
+                event_type: event.event_type,
+                source: event.source,
+                data: event.data,
+                timestamp: event.timestamp,
+                attempts: event.attempts,
+                last_worker: event.claimed_by,
+                dead_lettered_at: now,
+            });
+            continue;
+        }
+
+        let lease_expires_at = micros_after(now, EVENT_LEASE_MICROS);
+        events.id().update(Event {
+            claimed_by: Some(worker.clone()),
+            claimed_by_identity: Some(ctx.sender),
+            lease_expires_at: Some(lease_expires_at),
+            lease_expires_micros: lease_expires_at.to_micros_since_unix_epoch(),
+            attempts: event.attempts + 1,
+            ..event
+        });
+    }
+}
+
+/// Acknowledge an event, marking it processed
+/// Fails if `worker` doesn't hold a live lease on the event
+#[reducer]
+pub fn ack_event(
+    ctx: &spacetimedb::ReducerContext,
+    event_id: u64,
+    worker: String,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("ack_event");
+    let event = leased_event(ctx, event_id, &worker)?;
+
+    ctx.db.event_v2().id().update(Event {
+        processed: true,
+        claimed_by: None,
+        claimed_by_identity: None,
+        lease_expires_at: None,
+        lease_expires_micros: 0,
+        ..event
+    });
+    Ok(())
+}
+
+/// Give up a leased event so it can be redelivered right away
+/// Counts as a failed attempt; fails if `worker` doesn't hold a live lease
+#[reducer]
+pub fn nack_event(
+    ctx: &spacetimedb::ReducerContext,
+    event_id: u64,
+    worker: String,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("nack_event");
+    let event = leased_event(ctx, event_id, &worker)?;
+
+    ctx.db.event_v2().id().update(Event {
+        claimed_by: None,
+        claimed_by_identity: None,
+        lease_expires_at: None,
+        lease_expires_micros: 0,
+        ..event
+    });
+    Ok(())
+}
+
+/// Fetch an event that `worker` currently holds an unexpired lease on
+/// The caller must be the identity that claimed it; the worker name alone is
+/// caller-supplied and proves nothing
+fn leased_event(
+    ctx: &spacetimedb::ReducerContext,
+    event_id: u64,
+    worker: &str,
+) -> Result<Event, String> {
+    let event = ctx
+        .db
+        .event_v2()
+        .id()
+        .find(event_id)
+        .ok_or_else(|| format!("Event {event_id} not found"))?;
+
+    if event.processed {
+        return Err(format!("Event {event_id} is already processed"));
+    }
+    if event.claimed_by.as_deref() != Some(worker) {
+        return Err(format!(
+            "Event {event_id} is not leased to worker '{worker}'"
+        ));
+    }
+    if event.claimed_by_identity != Some(ctx.sender) {
+        return Err(format!(
+            "Event {event_id} was claimed by another identity, not {}",
+            ctx.sender
+        ));
+    }
+    if event
+        .lease_expires_at
+        .is_some_and(|expires| expires <= ctx.timestamp)
+    {
+        return Err(format!("Lease on event {event_id} has expired"));
+    }
+    Ok(event)
+}
+
+// ============================================================================
+// Reducers (Retention)
+// ============================================================================
+
+/// Longest max age `configure_retention` accepts, so the cutoff stays representable
+const MAX_RETENTION_AGE_SECS: u64 = (i64::MAX / 1_000_000) as u64;
+
+/// Timestamp `secs` seconds before `timestamp`, saturating at the earliest
+/// representable time
+fn secs_before(timestamp: Timestamp, secs: u64) -> Timestamp {
+    let micros = i64::try_from(secs.saturating_mul(1_000_000)).unwrap_or(i64::MAX);
+    Timestamp::from_micros_since_unix_epoch(
+        timestamp
+            .to_micros_since_unix_epoch()
+            .saturating_sub(micros),
+    )
+}
+
+/// Delete all messages older than `before_timestamp`
+/// Mirrors Convex `deleteOldMessages`; the deleted count is logged
+#[reducer]
+pub fn delete_old_messages(
+    ctx: &spacetimedb::ReducerContext,
+    before_timestamp: Timestamp,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("delete_old_messages");
+    require_admin(ctx, "delete_old_messages")?;
+    let deleted = ctx
+        .db
+        .message_v2()
+        .timestamp_micros()
+        .delete(..before_timestamp.to_micros_since_unix_epoch());
+    log::info!(
+        "Deleted {} messages older than {:?}",
+        deleted,
+        before_timestamp
+    );
+    Ok(())
+}
+
+/// Delete all events older than `before_timestamp`
+/// Mirrors Convex `deleteOldEvents`; the deleted count is logged
+#[reducer]
+pub fn delete_old_events(
+    ctx: &spacetimedb::ReducerContext,
+    before_timestamp: Timestamp,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("delete_old_events");
+    require_admin(ctx, "delete_old_events")?;
+    let deleted = ctx
+        .db
+        .event_v2()
+        .timestamp_micros()
+        .delete(..before_timestamp.to_micros_since_unix_epoch());
+    log::info!(
+        "Deleted {} events older than {:?}",
+        deleted,
+        before_timestamp
+    );
+    Ok(())
+}
+
+/// Enforce the configured max age and max row count on messages and events
+/// Only callable by the scheduler through `retention_schedule`
+#[reducer]
+pub fn run_retention(
+    ctx: &spacetimedb::ReducerContext,
+    _schedule: RetentionSchedule,
+) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("run_retention may only be invoked by the scheduler".to_string());
+    }
+
+    let settings = settings(ctx);
+    let messages = ctx.db.message_v2();
+    let events = ctx.db.event_v2();
+    let mut deleted_messages = 0;
+    let mut deleted_events = 0;
+
+    if settings.message_max_age_secs > 0 {
+        let cutoff = secs_before(ctx.timestamp, settings.message_max_age_secs);
+        deleted_messages += messages
+            .timestamp_micros()
+            .delete(..cutoff.to_micros_since_unix_epoch());
+    }
+    if settings.message_max_rows > 0 && messages.count() > settings.message_max_rows {
+        // Walk the timestamp index oldest first, reading only the excess rows
+        let excess = messages.count() - settings.message_max_rows;
+        let ids: Vec<u64> = messages
+            .timestamp_micros()
+            .filter(i64::MIN..)
+            .take(excess as usize)
+            .map(|m| m.id)
+            .collect();
+        for id in ids {
+            messages.id().delete(id);
+            deleted_messages += 1;
+        }
+    }
+
+    if settings.event_max_age_secs > 0 {
+        let cutoff = secs_before(ctx.timestamp, settings.event_max_age_secs);
+        deleted_events += events
+            .timestamp_micros()
+            .delete(..cutoff.to_micros_since_unix_epoch());
+    }
+    if settings.event_max_rows > 0 && events.count() > settings.event_max_rows {
+        let excess = events.count() - settings.event_max_rows;
+        let ids: Vec<u64> = events
+            .timestamp_micros()
+            .filter(i64::MIN..)
+            .take(excess as usize)
+            .map(|e| e.id)
+            .collect();
+        for id in ids {
+            events.id().delete(id);
+            deleted_events += 1;
+        }
+    }
+
+    if deleted_messages > 0 || deleted_events > 0 {
+        log::info!(
+            "Retention deleted {} messages and {} events",
+            deleted_messages,
+            deleted_events
+        );
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Reducers (Cleanup)
+// ============================================================================
+
+/// Tables `clear_data` can truncate
+const CLEARABLE_TABLES: [&str; 6] = [
+    "counter",
+    "message_v2",
+    "event_v2",
+    "heartbeat_tick",
+    "message_page",
+    "counter_lookup",
+];
+
+/// Delete every row of the named tables (any of `counter`, `message_v2`,
+/// `event_v2`, `heartbeat_tick`, `message_page`, `counter_lookup`)
+/// Logs how many rows each table had. Channels keep their sequence numbers,
+/// so `seq` stays monotonic across clears
+#[reducer]
+pub fn clear_data(ctx: &spacetimedb::ReducerContext, tables: Vec<String>) -> Result<(), String> {
+    require_admin(ctx, "clear_data")?;
+    if tables.is_empty() {
+        return Err(format!(
+            "No tables given, expected any of {CLEARABLE_TABLES:?}"
+        ));
+    }
+    if let Some(unknown) = tables
+        .iter()
+        .find(|t| !CLEARABLE_TABLES.contains(&t.as_str()))
+    {
+        return Err(format!(
+            "Unknown table '{unknown}', expected any of {CLEARABLE_TABLES:?}"
+        ));
+    }
+
+    for table in &tables {
+        let cleared = match table.as_str() {
+            "counter" => {
+                let counters = ctx.db.counter();
+                let names: Vec<String> = counters.iter().map(|c| c.name).collect();
+                for name in &names {
+                    counters.name().delete(name);
+                }
+                names.len()
+            }
+            "message_v2" => {
+                let messages = ctx.db.message_v2();
+                let ids: Vec<u64> = messages.iter().map(|m| m.id).collect();
+                for id in &ids {
+                    messages.id().delete(id);
+                }
+                ids.len()
+            }
+            "event_v2" => {
+                let events = ctx.db.event_v2();
+                let ids: Vec<u64> = events.iter().map(|e| e.id).collect();
+                for id in &ids {
+                    events.id().delete(id);
+                }
+                ids.len()
+            }
+            "heartbeat_tick" => {
+                let ticks = ctx.db.heartbeat_tick();
+                let ids: Vec<u64> = ticks.iter().map(|t| t.id).collect();
+                for id in &ids {
+                    ticks.id().delete(id);
+                }
+                ids.len()
+            }
+            "message_page" => {
+                let pages = ctx.db.message_page();
+                let ids: Vec<u64> = pages.iter().map(|p| p.id).collect();
+                for id in &ids {
+                    pages.id().delete(id);
+                }
+                ids.len()
+            }
+            "counter_lookup" => {
+                let lookups = ctx.db.counter_lookup();
+                let ids: Vec<u64> = lookups.iter().map(|l| l.id).collect();
+                for id in &ids {
+                    lookups.id().delete(id);
+                }
+                ids.len()
+            }
+            _ => unreachable!("table names are validated above"),
+        };
+        log::info!("Cleared {} rows from {}", cleared, table);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Reducers (Heartbeat)
+// ============================================================================
+
+/// Most `heartbeat_tick` rows kept; each firing past this trims the oldest
+const HEARTBEAT_TICKS_KEPT: u64 = 3600;
+
+/// Longest interval `configure_heartbeat` accepts (one day)
+const MAX_HEARTBEAT_INTERVAL_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// A heartbeat interval in microseconds, saturating rather than overflowing
+fn heartbeat_interval_micros(interval_ms: u64) -> i64 {
+    i64::try_from(interval_ms)
+        .unwrap_or(i64::MAX)
+        .saturating_mul(1000)
+}
+
+/// Schedule the next heartbeat to fire at `at`
+fn schedule_heartbeat(ctx: &spacetimedb::ReducerContext, at: Timestamp) {
+    ctx.db.heartbeat_schedule().insert(HeartbeatSchedule {
+        scheduled_id: 0, // Will be auto-generated
+        scheduled_at: spacetimedb::ScheduleAt::Time(at),
+    });
+}
+
+/// Record how late this heartbeat fired, then schedule the next one
+/// The next firing is based on the scheduled time rather than the actual
+/// one, so jitter doesn't accumulate into drift. Only callable by the scheduler
+#[reducer]
+pub fn heartbeat(
+    ctx: &spacetimedb::ReducerContext,
+    schedule: HeartbeatSchedule,
+) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("heartbeat may only be invoked by the scheduler".to_string());
+    }
+
+    let scheduled_for = match schedule.scheduled_at {
+        spacetimedb::ScheduleAt::Time(at) => at,
+        spacetimedb::ScheduleAt::Interval(_) => ctx.timestamp,
+    };
+    let ticks = ctx.db.heartbeat_tick();
+    ticks.insert(HeartbeatTick {
+        id: 0, // Will be auto-generated
+        scheduled_for,
+        fired_at: ctx.timestamp,
+        fired_at_micros: ctx.timestamp.to_micros_since_unix_epoch(),
+        jitter_micros: ctx.timestamp.to_micros_since_unix_epoch()
+            - scheduled_for.to_micros_since_unix_epoch(),
+    });
+    if ticks.count() > HEARTBEAT_TICKS_KEPT {
+        let excess = ticks.count() - HEARTBEAT_TICKS_KEPT;
+        let ids: Vec<u64> = ticks
+            .fired_at_micros()
+            .filter(i64::MIN..)
+            .take(excess as usize)
+            .map(|t| t.id)
+            .collect();
+        for id in ids {
+            ticks.id().delete(id);
+        }
+    }
+
+    let interval_ms = settings(ctx).heartbeat_interval_ms;
+    if interval_ms > 0 {
+        schedule_heartbeat(
+            ctx,
+            micros_after(scheduled_for, heartbeat_interval_micros(interval_ms)),
+        );
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Reducers (Benchmark Sessions)
+// ============================================================================
+
+/// Id of the active benchmark session, or 0 if none is running
+fn current_session(ctx: &spacetimedb::ReducerContext) -> u64 {
+    ctx.db
+        .benchmark_session()
+        .active()
+        .filter(true)
+        .next()
+        .map_or(0, |session| session.id)
+}
+
+/// Fetch a session, failing unless the caller created it or is an admin
+/// Admins can always clean up, so a crashed run can't block new sessions
+fn owned_session(
+    ctx: &spacetimedb::ReducerContext,
+    session_id: u64,
+) -> Result<BenchmarkSession, String> {
+    let session = ctx
+        .db
+        .benchmark_session()
+        .id()
+        .find(session_id)
+        .ok_or_else(|| format!("Benchmark session {session_id} not found"))?;
+
+    if session.created_by != ctx.sender && !is_admin(ctx, ctx.sender) {
+        return Err(format!(
+            "Benchmark session {session_id} was started by another identity; only it or an admin may change it"
+        ));
+    }
+    Ok(session)
+}
+
+/// Start a benchmark session; subsequent writes are attributed to it
+/// Fails if another session is still active
+#[reducer]
+pub fn start_benchmark_session(
+    ctx: &spacetimedb::ReducerContext,
+    name: String,
+    config: String,
+) -> Result<(), String> {
+    let active = current_session(ctx);
+    if active != 0 {
+        return Err(format!("Benchmark session {active} is still active"));
+    }
+
+    let session = ctx.db.benchmark_session().insert(BenchmarkSession {
+        id: 0, // Will be auto-generated
+        name,
+        config,
+        started_at: ctx.timestamp,
+        ended_at: None,
+        created_by: ctx.sender,
+        active: true,
+    });
+    log::info!(
+        "Started benchmark session {} '{}'",
+        session.id,
+        session.name
+    );
+    Ok(())
+}
+
+/// End a benchmark session started by the caller, or any session as an admin
+#[reducer]
+pub fn end_benchmark_session(
+    ctx: &spacetimedb::ReducerContext,
+    session_id: u64,
+) -> Result<(), String> {
+    let session = owned_session(ctx, session_id)?;
+    if !session.active {
+        return Err(format!("Benchmark session {session_id} has already ended"));
+    }
+
+    ctx.db.benchmark_session().id().update(BenchmarkSession {
+        ended_at: Some(ctx.timestamp),
+        active: false,
+        ..session
+    });
+    Ok(())
+}
+
+/// Delete a benchmark session started by the caller, or any session as an admin
+/// With `delete_data`, also deletes the counters, messages and events
+/// attributed to it, which additionally requires an admin
+#[reducer]
+pub fn delete_benchmark_session(
+    ctx: &spacetimedb::ReducerContext,
+    session_id: u64,
+    delete_data: bool,
+) -> Result<(), String> {
+    owned_session(ctx, session_id)?;
+    if delete_data {
+        require_admin(ctx, "delete_benchmark_session")?;
+    }
+    ctx.db.benchmark_session().id().delete(session_id);
+
+    if delete_data {
+        let counters = ctx.db.counter().session_id().delete(session_id);
+        let messages = ctx.db.message_v2().session_id().delete(session_id);
+        let events = ctx.db.event_v2().session_id().delete(session_id);
+        log::info!(
+            "Deleted benchmark session {} with {} counters, {} messages and {} events",
+            session_id,
+            counters,
+            messages,
+            events
+        );
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Reducers (Queries)
+// ============================================================================
+
+/// Largest page a message query may request
+const MAX_PAGE_SIZE: u32 = 1000;
+
+/// How long a query result is kept after its last refresh
+/// Anonymous HTTP callers get a new identity per call, so without expiry every
+/// read would leave a row behind
+const QUERY_RESULT_TTL_SECS: i64 = 5 * 60;
+
+/// Expiry for a query result written now
+fn query_result_expiry(ctx: &spacetimedb::ReducerContext) -> i64 {
+    micros_after(ctx.timestamp, QUERY_RESULT_TTL_SECS * 1_000_000).to_micros_since_unix_epoch()
+}
+
+/// `newest_first` key for a message written at `timestamp`
+fn newest_first_key(timestamp: Timestamp) -> i64 {
+    -timestamp.to_micros_since_unix_epoch()
+}
+
+/// Take the newest `limit` messages from `rows`, which must yield messages in
+/// ascending `newest_first` order, plus the cursor for the following page
+/// Reads at most `limit + 1` rows, plus the rest of the last timestamp's ties
+fn take_page(
+    rows: impl Iterator<Item = Message>,
+    limit: u32,
+) -> Result<(Vec<Message>, Option<MessageCursor>), String> {
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(format!(
+            "Limit must be between 1 and {MAX_PAGE_SIZE}, got {limit}"
+        ));
+    }
+
+    let limit = limit as usize;
+    let mut messages: Vec<Message> = Vec::with_capacity(limit + 1);
+    for message in rows {
+        // Rows sharing a timestamp come back in no particular order, so
+        // finish the last timestamp before ordering its ties by id
+        if messages.len() > limit
+            && messages
+                .last()
+                .is_some_and(|last| last.newest_first != message.newest_first)
+        {
+            break;
+        }
+        messages.push(message);
+    }
+    messages.sort_unstable_by_key(|m| (m.newest_first, Reverse(m.id)));
+
+    let next_cursor = if messages.len() > limit {
+        messages.truncate(limit);
+        messages.last().map(|m| MessageCursor {
+            timestamp: m.timestamp,
+            id: m.id,
+        })
+    } else {
+        None
+    };
+    Ok((messages, next_cursor))
+}
+
+/// Store a page as the caller's result for `request_id`
+fn store_message_page(
+    ctx: &spacetimedb::ReducerContext,
+    request_id: u64,
+    (messages, next_cursor): (Vec<Message>, Option<MessageCursor>),
+) {
+    let pages = ctx.db.message_page();
+    pages
+        .expires_at_micros()
+        .delete(..=ctx.timestamp.to_micros_since_unix_epoch());
+    let existing = pages
+        .caller_request()
+        .filter((&ctx.sender, request_id))
+        .next();
+    let page = MessagePage {
+        id: existing.as_ref().map_or(0, |p| p.id),
+        caller: ctx.sender,
+        request_id,
+        messages,
+        next_cursor,
+        fetched_at: ctx.timestamp,
+        expires_at_micros: query_result_expiry(ctx),
+    };
+    if existing.is_some() {
+        pages.id().update(page);
+    } else {
+        pages.insert(page);
+    }
+}
+
+/// Fetch a page of messages from a channel, newest first
+/// Mirrors Convex `getMessagesByChannel`; pass the previous page's
+/// `next_cursor` as `before` to page backwards
+#[reducer]
+pub fn get_messages(
+    ctx: &spacetimedb::ReducerContext,
+    channel: String,
+    limit: u32,
+    before: Option<MessageCursor>,
+    request_id: u64,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("get_messages");
+    let index = ctx.db.message_v2().channel_newest_first();
+    let page = match before {
+        Some(cursor) => {
+            let key = newest_first_key(cursor.timestamp);
+            let ties = index.filter((&channel, key)).filter(|m| m.id < cursor.id);
+            let older = index.filter((&channel, (Bound::Excluded(key), Bound::Unbounded)));
+            take_page(ties.chain(older), limit)?
+        }
+        None => take_page(index.filter(&channel), limit)?,
+    };
+    store_message_page(ctx, request_id, page);
+    Ok(())
+}
+
+/// Fetch a page of messages from a sender, newest first
+/// Mirrors Convex `getMessagesBySender`
+#[reducer]
+pub fn get_messages_by_sender(
+    ctx: &spacetimedb::ReducerContext,
+    sender: spacetimedb::Identity,
+    limit: u32,
+    before: Option<MessageCursor>,
+    request_id: u64,
+) -> Result<(), String> {
+    let _span = LogStopwatch::new("get_messages_by_sender");
+    let index = ctx.db.message_v2().sender_newest_first();
+    let page = match before {
+        Some(cursor) => {
+            let key = newest_first_key(cursor.timestamp);
+            let ties = index.filter((&sender, key)).filter(|m| m.id < cursor.id);
+            let older = index.filter((&sender, (Bound::Excluded(key), Bound::Unbounded)));
+            take_page(ties.chain(older), limit)?
+        }
+        None => take_page(index.filter(&sender), limit)?,
+    };
+    store_message_page(ctx, request_id, page);
+    Ok(())
+}
+
+/// Look up a counter by name
+/// Mirrors Convex `getCounterByName`; a missing counter is stored as `None`
+#[reducer]
+pub fn get_counter(ctx: &spacetimedb::ReducerContext, name: String, request_id: u64) {
+    let _span = LogStopwatch::new("get_counter");
+    let lookups = ctx.db.counter_lookup();
+    lookups
+        .expires_at_micros()
+        .delete(..=ctx.timestamp.to_micros_since_unix_epoch());
+    let existing = lookups
+        .caller_request()
+        .filter((&ctx.sender, request_id))
+        .next();
+    let lookup = CounterLookup {
+        id: existing.as_ref().map_or(0, |l| l.id),
+        caller: ctx.sender,
+        request_id,
+        counter: ctx.db.counter().name().find(&name),
+        name,
+        fetched_at: ctx.timestamp,
+        expires_at_micros: query_result_expiry(ctx),
+    };
+    if existing.is_some() {
+        lookups.id().update(lookup);
+    } else {
+        lookups.insert(lookup);
+    }
+}
+
+/// The caller's message pages, one per request id
+#[view(name = my_message_page, public)]
+pub fn my_message_page(ctx: &spacetimedb::ViewContext) -> Vec<MessagePage> {
+    ctx.db
+        .message_page()
+        .caller_request()
+        .filter(&ctx.sender)
+        .collect()
+}
+
+/// The caller's counter lookups, one per request id
+#[view(name = my_counter_lookup, public)]
+pub fn my_counter_lookup(ctx: &spacetimedb::ViewContext) -> Vec<CounterLookup> {
+    ctx.db
+        .counter_lookup()
+        .caller_request()
+        .filter(&ctx.sender)
+        .collect()
+}
+
+// ============================================================================
+// Reducers (Seed Data)
+// ============================================================================
+
+/// Seeded messages and events are spread over this window before `ctx.timestamp`
+const SEED_WINDOW_MICROS: i64 = 24 * 60 * 60 * 1_000_000;
+
+/// Sizes and seed for one `seed_data` run
+struct SeedSpec {
+    counters: u32,
+    messages_per_channel: u32,
+    channels: u32,
+    events: u32,
+    rng_seed: Option<u64>,
+}
+
+/// Data seeded by `init` when built with the `seed-on-init` feature
+#[cfg(feature = "seed-on-init")]
+const INIT_SEED: SeedSpec = SeedSpec {
+    counters: 1_000,
+    messages_per_channel: 100,
+    channels: 20,
+    events: 10_000,
+    rng_seed: Some(42),
+};
+
+/// Populate counters, messages and events with realistic synthetic data
+/// Channel popularity is Zipfian (channel `k` gets weight `1/k`), with
+/// `messages_per_channel * channels` messages in total; content lengths are
+/// mostly short with a long tail; messages and events are spread over the
+/// last 24 hours. With `rng_seed` set the output is fully deterministic,
+/// otherwise `ctx.rng()` is used
+#[reducer]
+pub fn seed_data(
+    ctx: &spacetimedb::ReducerContext,
+    counters: u32,
+    messages_per_channel: u32,
+    channels: u32,
+    events: u32,
+    rng_seed: Option<u64>,
+) -> Result<(), String> {
+    require_admin(ctx, "seed_data")?;
+    if messages_per_channel > 0 && channels == 0 {
+        return Err("channels must be at least 1 to seed messages".to_string());
+    }
+
+    generate_seed_data(
+        ctx,
+        &SeedSpec {
+            counters,
+            messages_per_channel,
+            channels,
+            events,
+            rng_seed,
+        },
+    );
+    Ok(())
+}
+
+/// Run a seed spec with either a seeded generator or the reducer's rng
+fn generate_seed_data(ctx: &spacetimedb::ReducerContext, spec: &SeedSpec) {
+    match spec.rng_seed {
+        Some(seed) => seed_with(ctx, spec, &mut StdRng::seed_from_u64(seed)),
+        None => seed_with(ctx, spec, &mut ctx.rng()),
+    }
+    log::info!(
+        "Seeded {} counters, {} messages across {} channels and {} events",
+        spec.counters,
+        spec.messages_per_channel as u64 * spec.channels as u64,
+        spec.channels,
+        spec.events
+    );
+}
+
+/// Random timestamp within the seed window ending at `now`
+fn seed_timestamp(rng: &mut impl Rng, now: Timestamp) -> Timestamp {
+    micros_after(now, -rng.gen_range(0..SEED_WINDOW_MICROS))
+}
+
+/// Random alphanumeric content: mostly short, some medium, a few long
+fn seed_content(rng: &mut impl Rng) -> String {
+    let len = match rng.gen_range(0..100) {
+        0..=69 => rng.gen_range(10..80),
+        70..=94 => rng.gen_range(80..500),
+        _ => rng.gen_range(500..4000),
+    };
+    rng.sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// Insert the rows described by `spec`, drawing all randomness from `rng`
+fn seed_with(ctx: &spacetimedb::ReducerContext, spec: &SeedSpec, rng: &mut impl Rng) {
+    let now = ctx.timestamp;
+    let session_id = current_session(ctx);
+
+    let counters = ctx.db.counter();
+    for i in 0..spec.counters {
+        let name = format!("seed_counter_{i}");
+        if counters.name().find(&name).is_none() {
+            counters.insert(Counter {
+                name,
+                value: rng.gen_range(0..10_000),
+                version: 0,
+                last_updated: seed_timestamp(rng, now),
+                session_id,
+                owner: None,
+            });
+        }
+    }
+
+    // Zipfian channel popularity: cumulative weights 1/1, 1/2, ..., 1/n
+    let cumulative: Vec<f64> = (1..=spec.channels)
+        .scan(0.0, |total, k| {
+            *total += 1.0 / k as f64;
+            Some(*total)
+        })
+        .collect();
+    let total_weight = cumulative.last().copied().unwrap_or(0.0);
+
+    // Insert in timestamp order so each channel's `seq` follows time order
+    let total_messages = spec.messages_per_channel as usize * spec.channels as usize;
+    let mut messages: Vec<(Timestamp, usize)> = (0..total_messages)
+        .map(|_| {
+            let pick = rng.gen_range(0.0..total_weight);
+            let channel = cumulative.partition_point(|&w| w <= pick);
+            (seed_timestamp(rng, now), channel.min(cumulative.len() - 1))
+        })
+        .collect();
+    messages.sort_unstable();
+
+    for (timestamp, channel) in messages {
+        let channel = format!("seed_channel_{channel}");
+        let seq = next_channel_seq(ctx, &channel);
+        ctx.db.message_v2().insert(Message {
+            id: 0, // Will be auto-generated
+            sender: ctx.sender,
+            sender_name: Some(format!("seed_user_{}", rng.gen_range(0..1000))),
+            content: seed_content(rng),
+            channel,
+            seq,
+            priority: rng.gen_bool(0.2).then(|| rng.gen_range(0..5)),
+            tags: Vec::new(),
+            timestamp,
+            timestamp_micros: timestamp.to_micros_since_unix_epoch(),
+            newest_first: newest_first_key(timestamp),
+            session_id,
+        });
+    }
+
+    const EVENT_TYPES: [&str; 4] = ["cpu_usage", "memory_usage", "request_count", "error_rate"];
+    const EVENT_UNITS: [&str; 4] = ["percent", "bytes", "count", "percent"];
+    for _ in 0..spec.events {
+        let kind = rng.gen_range(0..EVENT_TYPES.len());
+        let timestamp = seed_timestamp(rng, now);
+        ctx.db.event_v2().insert(Event {
+            id: 0, // Will be auto-generated
+            event_type: EVENT_TYPES[kind].to_string(),
+            source: format!("seed_host_{}", rng.gen_range(0..50)),
+            data: EventPayload {
+                value: rng.gen_range(0.0..100.0),
+                unit: Some(EVENT_UNITS[kind].to_string()),
+                tags: Vec::new(),
+            },
+            timestamp,
+            timestamp_micros: timestamp.to_micros_since_unix_epoch(),
+            processed: false,
+            claimed_by: None,
+            claimed_by_identity: None,
+            lease_expires_at: None,
+            lease_expires_micros: 0,
+            attempts: 0,
+            session_id,
+        });
+    }
+}
+
+// ============================================================================
+// Settings
+// ============================================================================
+
+/// Primary key of the singleton `settings` row
+const SETTINGS_ID: u32 = 0;
+
+/// Current module settings, or the defaults if none have been stored
+fn settings(ctx: &spacetimedb::ReducerContext) -> Settings {
+    ctx.db.settings().id().find(SETTINGS_ID).unwrap_or_default()
+}
+
+/// Store the module settings, creating the singleton row if needed
+fn save_settings(ctx: &spacetimedb::ReducerContext, settings: Settings) {
+    let table = ctx.db.settings();
+    if table.id().find(SETTINGS_ID).is_some() {
+        table.id().update(settings);
+    } else {
+        table.insert(settings);
+    }
+}
+
+/// Set the largest batch `create_messages_batch`/`create_events_batch` accept
+#[reducer]
+pub fn configure_batching(
+    ctx: &spacetimedb::ReducerContext,
+    max_batch_size: u32,
+) -> Result<(), String> {
+    require_admin(ctx, "configure_batching")?;
+    if max_batch_size == 0 {
+        return Err("max_batch_size must be at least 1".to_string());
+    }
+    save_settings(
+        ctx,
+        Settings {
+            max_batch_size,
+            ..settings(ctx)
+        },
+    );
+    Ok(())
+}
+
+/// Set retention limits for messages and events (0 disables a limit)
+/// Reschedules `run_retention` every `interval_secs`, or stops it when all
+/// limits are disabled
+#[reducer]
+pub fn configure_retention(
+    ctx: &spacetimedb::ReducerContext,
+    message_max_age_secs: u64,
+    message_max_rows: u64,
+    event_max_age_secs: u64,
+    event_max_rows: u64,
+    interval_secs: u64,
+) -> Result<(), String> {
+    require_admin(ctx, "configure_retention")?;
+    if interval_secs == 0 {
+        return Err("interval_secs must be at least 1".to_string());
+    }
+    if message_max_age_secs > MAX_RETENTION_AGE_SECS || event_max_age_secs > MAX_RETENTION_AGE_SECS
+    {
+        return Err(format!(
+            "Max age must be at most {MAX_RETENTION_AGE_SECS} seconds"
+        ));
+    }
+
+    save_settings(
+        ctx,
+        Settings {
+            message_max_age_secs,
+            message_max_rows,
+            event_max_age_secs,
+            event_max_rows,
+            retention_interval_secs: interval_secs,
+            ..settings(ctx)
+        },
+    );
+
+    let schedules = ctx.db.retention_schedule();
+    let scheduled_ids: Vec<u64> = schedules.iter().map(|s| s.scheduled_id).collect();
+    for scheduled_id in scheduled_ids {
+        schedules.scheduled_id().delete(scheduled_id);
+    }
+
+    let enabled = message_max_age_secs > 0
+        || message_max_rows > 0
+        || event_max_age_secs > 0
+        || event_max_rows > 0;
+    if enabled {
+        schedules.insert(RetentionSchedule {
+            scheduled_id: 0, // Will be auto-generated
+            scheduled_at: std::time::Duration::from_secs(interval_secs).into(),
+        });
+    }
+    Ok(())
+}
+
+/// Set how often the heartbeat fires (0 stops it, at most one day)
+/// Replaces any pending heartbeat; the next one fires one interval from now
+#[reducer]
+pub fn configure_heartbeat(
+    ctx: &spacetimedb::ReducerContext,
+    interval_ms: u64,
+) -> Result<(), String> {
+    require_admin(ctx, "configure_heartbeat")?;
+    if interval_ms > MAX_HEARTBEAT_INTERVAL_MS {
+        return Err(format!(
+            "interval_ms of {interval_ms} exceeds the maximum of {MAX_HEARTBEAT_INTERVAL_MS}"
+        ));
+    }
+    save_settings(
+        ctx,
+        Settings {
+            heartbeat_interval_ms: interval_ms,
+            ..settings(ctx)
+        },
+    );
+
+    let schedules = ctx.db.heartbeat_schedule();
+    let scheduled_ids: Vec<u64> = schedules.iter().map(|s| s.scheduled_id).collect();
+    for scheduled_id in scheduled_ids {
+        schedules.scheduled_id().delete(scheduled_id);
+    }
+
+    if interval_ms > 0 {
+        schedule_heartbeat(
+            ctx,
+            micros_after(ctx.timestamp, heartbeat_interval_micros(interval_ms)),
+        );
+    }
+    Ok(())
+}
+
+/// Turn counter ownership checks on or off
+/// Off lets any caller write owned counters, giving the no-authorization baseline
+#[reducer]
+pub fn configure_authorization(
+    ctx: &spacetimedb::ReducerContext,
+    enforce_counter_ownership: bool,
+) -> Result<(), String> {
+    require_admin(ctx, "configure_authorization")?;
+    save_settings(
+        ctx,
+        Settings {
+            enforce_counter_ownership,
+            ..settings(ctx)
+        },
+    );
+    Ok(())
+}
+
+// ============================================================================
+// Migrations
+// ============================================================================
+
+/// Primary key of the singleton `schema_version` row
+const SCHEMA_VERSION_ID: u32 = 0;
+
+/// One schema migration step
+/// Steps must be idempotent: re-running one on already-migrated data writes
+/// nothing. `run` returns the number of rows it wrote
+struct Migration {
+    version: u32,
+    name: &'static str,
+    run: fn(&spacetimedb::ReducerContext) -> u64,
+}
+
+/// All migration steps, in the order they apply
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "copy_legacy_messages",
+        run: copy_legacy_messages,
+    },
+    Migration {
+        version: 2,
+        name: "copy_legacy_events",
+        run: copy_legacy_events,
+    },
+    Migration {
+        version: 3,
+        name: "materialize_sharded_counter_summaries",
+        run: materialize_sharded_counter_summaries,
+    },
+];
+
+/// Version after every migration in `MIGRATIONS` has run
+fn latest_schema_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Move rows of the legacy `message` table into `message_v2`, in id order
+/// The legacy sender was a caller-supplied name, so it becomes `sender_name`
+/// and `sender` is left as `Identity::ZERO`. Channels are registered and
+/// `seq` numbers assigned as if the messages were posted again
+fn copy_legacy_messages(ctx: &spacetimedb::ReducerContext) -> u64 {
+    let legacy = ctx.db.message();
+    let mut pending: Vec<LegacyMessage> = legacy.iter().collect();
+    pending.sort_unstable_by_key(|m| m.id);
+
+    let copied = pending.len() as u64;
+    for message in pending {
+        legacy.id().delete(message.id);
+        let seq = next_channel_seq(ctx, &message.channel);
+        ctx.db.message_v2().insert(Message {
+            id: 0, // Will be auto-generated
+            sender: spacetimedb::Identity::ZERO,
+            sender_name: Some(message.sender).filter(|name| !name.is_empty()),
+            content: message.content,
+            channel: message.channel,
+            seq,
+            priority: None,
+            tags: Vec::new(),
+            timestamp: message.timestamp,
+            timestamp_micros: message.timestamp.to_micros_since_unix_epoch(),
+            newest_first: newest_first_key(message.timestamp),
+            session_id: 0,
+        });
+    }
+    copied
+}
+
+/// Move rows of the legacy `event` table into `event_v2`, in id order
+/// Their JSON `data` is converted by `legacy_event_payload`; copied events
+/// start unclaimed
+fn copy_legacy_events(ctx: &spacetimedb::ReducerContext) -> u64 {
+    let legacy = ctx.db.event();
+    let mut pending: Vec<LegacyEvent> = legacy.iter().collect();
+    pending.sort_unstable_by_key(|e| e.id);
+
+    let copied = pending.len() as u64;
+    for event in pending {
+        legacy.id().delete(event.id);
+        ctx.db.event_v2().insert(Event {
+            id: 0, // Will be auto-generated
+            event_type: event.event_type,
+            source: event.source,
+            data: legacy_event_payload(&event.data),
+            timestamp: event.timestamp,
+            timestamp_micros: event.timestamp.to_micros_since_unix_epoch(),
+            processed: false,
+            claimed_by: None,
+            claimed_by_identity: None,
+            lease_expires_at: None,
+            lease_expires_micros: 0,
+            attempts: 0,
+            session_id: 0,
+        });
+    }
+    copied
+}
+
+/// Create a summary row for every sharded counter that doesn't have one
+/// Counters whose shards sum past `i64` are logged and left without one
+fn materialize_sharded_counter_summaries(ctx: &spacetimedb::ReducerContext) -> u64 {
+    let mut totals: std::collections::BTreeMap<String, (u32, i128)> = Default::default();
+    for row in ctx.db.sharded_counter().iter() {
+        let (shards, total) = totals.entry(row.name).or_default();
+        *shards += 1;
+        *total += row.value as i128;
+    }
+
+    let summaries = ctx.db.sharded_counter_summary();
+    let mut created = 0;
+    for (name, (shard_count, total)) in totals {
+        let Ok(value) = i64::try_from(total) else {
+            log::warn!(
+                "Sharded counter '{}' sums to {}, which overflows",
+                name,
+                total
+            );
+            continue;
+        };
+        if summaries.name().find(&name).is_none() {
+            summaries.insert(ShardedCounterSummary {
+                name,
+                value,
+                shard_count,
+                materialized_at: ctx.timestamp,
+            });
+            created += 1;
+        }
+    }
+    created
+}
+
+/// Record `version` as the current schema version
+fn set_schema_version(ctx: &spacetimedb::ReducerContext, version: u32) {
+    let row = SchemaVersion {
+        id: SCHEMA_VERSION_ID,
+        version,
+        updated_at: ctx.timestamp,
+    };
+    let versions = ctx.db.schema_version();
+    if versions.id().find(SCHEMA_VERSION_ID).is_some() {
+        versions.id().update(row);
+    } else {
+        versions.insert(row);
+    }
+}
+
+/// Apply every migration newer than the stored schema version, in order
+/// Each step writes a `migration_log` row and bumps the version, all in
+/// one transaction, so a failed upgrade leaves the data untouched
+fn run_migrations(ctx: &spacetimedb::ReducerContext) {
+    let current = ctx
+        .db
+        .schema_version()
+        .id()
+        .find(SCHEMA_VERSION_ID)
+        .map_or(0, |v| v.version);
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let rows_affected = (migration.run)(ctx);
+        ctx.db.migration_log().insert(MigrationLog {
+            id: 0, // Will be auto-generated
+            version: migration.version,
+            name: migration.name.to_string(),
+            rows_affected,
+            applied_at: ctx.timestamp,
+        });
+        set_schema_version(ctx, migration.version);
+        log::info!(
+            "Applied migration {} '{}' ({} rows)",
+            migration.version,
+            migration.name,
+            rows_affected
+        );
+    }
+}
+
+// ============================================================================
+// Connection Tracking
+// ============================================================================
+
+/// Primary key of the singleton `connection_stats` row
+const CONNECTION_STATS_ID: u32 = 0;
+
+/// Apply `update` to the connection stats, creating the row if needed
+fn update_connection_stats(
+    ctx: &spacetimedb::ReducerContext,
+    update: impl FnOnce(&mut ConnectionStats),
+) {
+    let table = ctx.db.connection_stats();
+    let existing = table.id().find(CONNECTION_STATS_ID);
+    let is_new = existing.is_none();
+    let mut stats = existing.unwrap_or(ConnectionStats {
+        id: CONNECTION_STATS_ID,
+        current_connections: 0,
+        peak_connections: 0,
+        total_connections: 0,
+        total_disconnections: 0,
+        updated_at: ctx.timestamp,
+    });
+
+    update(&mut stats);
+    stats.updated_at = ctx.timestamp;
+
+    if is_new {
+        table.insert(stats);
+    } else {
+        table.id().update(stats);
+    }
+}
+
+/// Called when a client connects
+#[reducer(client_connected)]
+pub fn client_connected(ctx: &spacetimedb::ReducerContext) {
+    let Some(connection_id) = ctx.connection_id else {
+        return;
+    };
+
+    ctx.db.connection().insert(Connection {
+        connection_id,
+        identity: ctx.sender,
+        connected_at: ctx.timestamp,
+        disconnected_at: None,
+    });
+    update_connection_stats(ctx, |stats| {
+        stats.current_connections += 1;
+        stats.total_connections += 1;
+        stats.peak_connections = stats.peak_connections.max(stats.current_connections);
+    });
+}
+
+/// Called when a client disconnects
+#[reducer(client_disconnected)]
+pub fn client_disconnected(ctx: &spacetimedb::ReducerContext) {
+    let Some(connection_id) = ctx.connection_id else {
+        return;
+    };
+
+    let connections = ctx.db.connection();
+    let Some(connection) = connections.connection_id().find(connection_id) else {
+        // Connected before connection tracking was deployed
+        return;
+    };
+
+    connections.connection_id().update(Connection {
+        disconnected_at: Some(ctx.timestamp),
+        ..connection
+    });
+    update_connection_stats(ctx, |stats| {
+        stats.current_connections = stats.current_connections.saturating_sub(1);
+        stats.total_disconnections += 1;
+    });
+}
+
+// ============================================================================
+// Access Control
+// ============================================================================
+
+/// Error returned when a non-admin calls an admin-only reducer
+#[derive(Debug)]
+pub struct NotAdmin {
+    /// Identity that made the call
+    pub caller: spacetimedb::Identity,
+    /// Reducer that was refused
+    pub reducer: &'static str,
+}
+
+impl std::fmt::Display for NotAdmin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Permission denied: '{}' requires an admin, {} is not one",
+            self.reducer, self.caller
+        )
+    }
+}
+
+impl From<NotAdmin> for String {
+    fn from(err: NotAdmin) -> Self {
+        err.to_string()
+    }
+}
+
+/// Whether `identity` is in the `admin` table
+fn is_admin(ctx: &spacetimedb::ReducerContext, identity: spacetimedb::Identity) -> bool {
+    ctx.db.admin().identity().find(identity).is_some()
+}
+
+/// Fail with `NotAdmin` unless the caller is an admin
+fn require_admin(ctx: &spacetimedb::ReducerContext, reducer: &'static str) -> Result<(), NotAdmin> {
+    if is_admin(ctx, ctx.sender) {
+        Ok(())
+    } else {
+        Err(NotAdmin {
+            caller: ctx.sender,
+            reducer,
+        })
+    }
+}
+
+/// Identity that owns the database, from `BENCHMARK_OWNER_IDENTITY` at build time
+/// A module can't look up its owner at runtime, so an upgraded database with
+/// no admins can only be bootstrapped when this is compiled in
+fn database_owner() -> Option<spacetimedb::Identity> {
+    option_env!("BENCHMARK_OWNER_IDENTITY")
+        .and_then(|hex| spacetimedb::Identity::from_hex(hex.trim()).ok())
+}
+
+/// Grant the admin role to the caller without checks
+/// Only for bootstrapping the first admin
+fn insert_first_admin(ctx: &spacetimedb::ReducerContext) {
+    ctx.db.admin().insert(Admin {
+        identity: ctx.sender,
+        added_by: ctx.sender,
+        added_at: ctx.timestamp,
+    });
+    log::info!("Seeded {} as the first admin", ctx.sender);
+}
+
+/// Grant the admin role to `identity`
+/// Fails if the caller isn't an admin or `identity` already is one
+#[reducer]
+pub fn add_admin(
+    ctx: &spacetimedb::ReducerContext,
+    identity: spacetimedb::Identity,
+) -> Result<(), String> {
+    require_admin(ctx, "add_admin")?;
+    if is_admin(ctx, identity) {
+        return Err(format!("{identity} is already an admin"));
+    }
+
+    ctx.db.admin().insert(Admin {
+        identity,
+        added_by: ctx.sender,
+        added_at: ctx.timestamp,
+    });
+    Ok(())
+}
+
+/// Revoke the admin role from `identity`
+/// Fails if the caller isn't an admin, `identity` isn't one, or it is the
+/// last admin left
+#[reducer]
+pub fn remove_admin(
+    ctx: &spacetimedb::ReducerContext,
+    identity: spacetimedb::Identity,
+) -> Result<(), String> {
+    require_admin(ctx, "remove_admin")?;
+    let admins = ctx.db.admin();
+    if !is_admin(ctx, identity) {
+        return Err(format!("{identity} is not an admin"));
+    }
+    if admins.count() == 1 {
+        return Err("Cannot remove the last admin".to_string());
+    }
+
+    admins.identity().delete(identity);
+    Ok(())
+}
+
+// ============================================================================
+// Rate Limiting
+// ============================================================================
+
+/// Error returned when a caller's token bucket for a reducer is empty
+#[derive(Debug)]
+pub struct RateLimited {
+    /// Identity that made the call
+    pub caller: spacetimedb::Identity,
+    /// Reducer that was refused
+    pub reducer: &'static str,
+    /// How long until the bucket holds a whole token again
+    pub retry_after_micros: u64,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Rate limited: '{}' for {}, retry after {} us",
+            self.reducer, self.caller, self.retry_after_micros
+        )
+    }
+}
+
+impl From<RateLimited> for String {
+    fn from(err: RateLimited) -> Self {
+        err.to_string()
+    }
+}
+
+/// Spend one token from the caller's bucket for `reducer`, refilling it first
+/// Buckets start full at `rate_limit_burst` and refill at `rate_limit_per_sec`;
+/// a rejected call rolls back with the reducer, so it spends nothing
+fn take_rate_limit_token(
+    ctx: &spacetimedb::ReducerContext,
+    reducer: &'static str,
+) -> Result<(), RateLimited> {
+    let settings = settings(ctx);
+    if settings.rate_limit_per_sec == 0 {
+        return Ok(());
+    }
+    let rate = settings.rate_limit_per_sec as f64;
+    let capacity = settings.rate_limit_burst.max(1) as f64;
+
+    let buckets = ctx.db.rate_limit_bucket();
+    let mut bucket = buckets
+        .identity_reducer()
+        .filter((&ctx.sender, reducer))
+        .next()
+        .unwrap_or_else(|| RateLimitBucket {
+            id: 0, // Will be auto-generated
+            identity: ctx.sender,
+            reducer: reducer.to_string(),
+            tokens: capacity,
+            refilled_at: ctx.timestamp,
+        });
+
+    let elapsed_micros = ctx.timestamp.to_micros_since_unix_epoch()
+        - bucket.refilled_at.to_micros_since_unix_epoch();
+    let refill = elapsed_micros.max(0) as f64 / 1_000_000.0 * rate;
+    bucket.tokens = (bucket.tokens + refill).min(capacity);
+    bucket.refilled_at = ctx.timestamp;
+
+    if bucket.tokens < 1.0 {
+        return Err(RateLimited {
+            caller: ctx.sender,
+            reducer,
+            retry_after_micros: ((1.0 - bucket.tokens) / rate * 1_000_000.0).ceil() as u64,
+        });
+    }
+    bucket.tokens -= 1.0;
+
+    if bucket.id == 0 {
+        buckets.insert(bucket);
+    } else {
+        buckets.id().update(bucket);
+    }
+    Ok(())
+}
+
+/// Set the per-identity rate limit on the message, event and counter write
+/// reducers (`per_sec` 0 disables it)
+/// Existing buckets are cleared so every identity starts with a full burst
+#[reducer]
+pub fn configure_rate_limit(
+    ctx: &spacetimedb::ReducerContext,
+    per_sec: u32,
+    burst: u32,
+) -> Result<(), String> {
+    require_admin(ctx, "configure_rate_limit")?;
+    if per_sec > 0 && burst == 0 {
+        return Err("burst must be at least 1".to_string());
+    }
+    save_settings(
+        ctx,
+        Settings {
+            rate_limit_per_sec: per_sec,
+            rate_limit_burst: burst,
+            ..settings(ctx)
+        },
+    );
+
+    let buckets = ctx.db.rate_limit_bucket();
+    let ids: Vec<u64> = buckets.iter().map(|b| b.id).collect();
+    for id in ids {
+        buckets.id().delete(id);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Idempotency
+// ============================================================================
+
+/// `scoped_key` for `key` applied through `reducer`
+fn scoped_idempotency_key(reducer: &str, key: &str) -> String {
+    format!("{reducer}/{key}")
+}
+
+/// Whether `key` was already applied through `reducer` and hasn't expired yet
+/// Expired records are purged first, so the table only holds live keys
+fn is_duplicate_key(
+    ctx: &spacetimedb::ReducerContext,
+    reducer: &str,
+    key: Option<&String>,
+) -> bool {
+    let Some(key) = key else {
+        return false;
+    };
+
+    let records = ctx.db.idempotency_record();
+    records
+        .expires_at_micros()
+        .delete(..=ctx.timestamp.to_micros_since_unix_epoch());
+    match records
+        .scoped_key()
+        .find(scoped_idempotency_key(reducer, key))
+    {
+        Some(record) => {
+            log::info!(
+                "Skipped repeated idempotency key '{}' for {} (already applied as row {})",
+                key,
+                reducer,
+                record.row_id
+            );
+            true
+        }
+        None => false,
+    }
+}
+
+/// Remember `key` as applied by `reducer` for `idempotency_ttl_secs`
+fn record_idempotency_key(
+    ctx: &spacetimedb::ReducerContext,
+    key: String,
+    reducer: &str,
+    row_id: u64,
+) {
+    let ttl_micros = i64::try_from(settings(ctx).idempotency_ttl_secs.saturating_mul(1_000_000))
+        .unwrap_or(i64::MAX);
+    ctx.db.idempotency_record().insert(IdempotencyRecord {
+        id: 0, // Will be auto-generated
+        scoped_key: scoped_idempotency_key(reducer, &key),
+        caller: ctx.sender,
+        row_id,
+        expires_at_micros: ctx
+            .timestamp
+            .to_micros_since_unix_epoch()
+            .saturating_add(ttl_micros),
+    });
+}
+
+/// Set how long idempotency keys are remembered
+/// Applies to keys recorded from now on
+#[reducer]
+pub fn configure_idempotency(
+    ctx: &spacetimedb::ReducerContext,
+    ttl_secs: u64,
+) -> Result<(), String> {
+    require_admin(ctx, "configure_idempotency")?;
+    if ttl_secs == 0 {
+        return Err("ttl_secs must be at least 1".to_string());
+    }
+    save_settings(
+        ctx,
+        Settings {
+            idempotency_ttl_secs: ttl_secs,
+            ..settings(ctx)
+        },
+    );
+    Ok(())
+}
+
+// ============================================================================
+// Initialization
+// ============================================================================
+
+/// Called when the module is first published/initialized
+#[reducer(init)]
+pub fn init(ctx: &spacetimedb::ReducerContext) {
+    // The publishing identity administers the database
+    insert_first_admin(ctx);
+
+    // Start the heartbeat at the default interval
+    let interval_ms = settings(ctx).heartbeat_interval_ms;
+    if interval_ms > 0 {
+        schedule_heartbeat(
+            ctx,
+            micros_after(ctx.timestamp, heartbeat_interval_micros(interval_ms)),
+        );
+    }
+
+    // A fresh database has nothing to migrate
+    set_schema_version(ctx, latest_schema_version());
+
+    #[cfg(feature = "seed-on-init")]
+    generate_seed_data(ctx, &INIT_SEED);
+}
+
+/// Called when the module is updated to a new version
+/// Brings existing data up to the current schema; run after every
+/// non-destructive publish (`./deploy.sh --upgrade` does this).
+/// Admin only; a database published before admins existed has none, so
+/// the database owner may run it once to become the first admin
+#[reducer]
+pub fn on_module_update(ctx: &spacetimedb::ReducerContext) -> Result<(), String> {
+    if ctx.db.admin().count() == 0 {
+        if database_owner() != Some(ctx.sender) {
+            return Err(format!(
+                "No admins yet: only the database owner may bootstrap one, {} is not it \
+                 (build with BENCHMARK_OWNER_IDENTITY set to the owner's identity)",
+                ctx.sender
+            ));
+        }
+        insert_first_admin(ctx);
+    }
+    require_admin(ctx, "on_module_update")?;
+    run_migrations(ctx);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(micros: i64) -> Timestamp {
+        Timestamp::from_micros_since_unix_epoch(micros)
+    }
+
+    #[test]
+    fn secs_before_subtracts_seconds() {
+        assert_eq!(secs_before(at(5_000_000), 2), at(3_000_000));
+        assert_eq!(secs_before(at(5_000_000), 0), at(5_000_000));
+    }
+
+    #[test]
+    fn secs_before_saturates() {
+        assert_eq!(secs_before(at(0), u64::MAX), at(-i64::MAX));
+        assert_eq!(secs_before(at(-1), u64::MAX), at(i64::MIN));
+        assert_eq!(secs_before(at(i64::MIN + 1), 1), at(i64::MIN));
+        assert_eq!(
+            secs_before(at(0), MAX_RETENTION_AGE_SECS),
+            at(-(MAX_RETENTION_AGE_SECS as i64 * 1_000_000))
+        );
+    }
+
+    #[test]
+    fn micros_after_adds_either_direction() {
+        assert_eq!(micros_after(at(1_000), 500), at(1_500));
+        assert_eq!(micros_after(at(1_000), -1_500), at(-500));
+    }
+
+    #[test]
+    fn micros_after_saturates() {
+        assert_eq!(micros_after(at(i64::MAX - 1), 10), at(i64::MAX));
+        assert_eq!(micros_after(at(i64::MIN + 1), -10), at(i64::MIN));
+    }
+
+    #[test]
+    fn heartbeat_interval_micros_saturates() {
+        assert_eq!(heartbeat_interval_micros(250), 250_000);
+        assert_eq!(heartbeat_interval_micros(u64::MAX), i64::MAX);
+        assert_eq!(heartbeat_interval_micros(i64::MAX as u64 / 999), i64::MAX);
+    }
+
+    fn message(id: u64, micros: i64) -> Message {
+        Message {
+            id,
+            sender: spacetimedb::Identity::ZERO,
+            sender_name: None,
+            content: String::new(),
+            channel: "general".to_string(),
+            seq: id,
+            priority: None,
+            tags: Vec::new(),
+            timestamp: at(micros),
+            timestamp_micros: micros,
+            newest_first: newest_first_key(at(micros)),
+            session_id: 0,
+        }
+    }
+
+    fn ids(messages: &[Message]) -> Vec<u64> {
+        messages.iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn take_page_rejects_bad_limits() {
+        assert!(take_page(std::iter::empty(), 0).is_err());
+        assert!(take_page(std::iter::empty(), MAX_PAGE_SIZE + 1).is_err());
+    }
+
+    #[test]
+    fn take_page_orders_ties_by_id_across_the_cursor() {
+        // Index order: newest first, ties at 20 in no particular order
+        let rows = || {
+            vec![
+                message(5, 30),
+                message(3, 20),
+                message(4, 20),
+                message(2, 20),
+                message(1, 10),
+            ]
+        };
+
+        // The page ends inside the tie, so all of it is read before sorting
+        let (page, cursor) = take_page(rows().into_iter(), 2).unwrap();
+        assert_eq!(ids(&page), [5, 4]);
+        let cursor = cursor.unwrap();
+        assert_eq!(
+            cursor,
+            MessageCursor {
+                timestamp: at(20),
+                id: 4
+            }
+        );
+
+        // Resume as `get_messages` does: the rest of the tie, then older rows
+        let resume = |cursor: &MessageCursor| {
+            let key = newest_first_key(cursor.timestamp);
+            let ties = rows()
+                .into_iter()
+                .filter(move |m| m.newest_first == key && m.id < cursor.id);
+            let older = rows().into_iter().filter(move |m| m.newest_first > key);
+            ties.chain(older).collect::<Vec<_>>()
+        };
+        let (page, cursor) = take_page(resume(&cursor).into_iter(), 2).unwrap();
+        assert_eq!(ids(&page), [3, 2]);
+        let cursor = cursor.unwrap();
+        assert_eq!(
+            cursor,
+            MessageCursor {
+                timestamp: at(20),
+                id: 2
+            }
+        );
+
+        let (page, cursor) = take_page(resume(&cursor).into_iter(), 2).unwrap();
+        assert_eq!(ids(&page), [1]);
+        assert_eq!(cursor, None);
+    }
+
+    #[test]
+    fn take_page_without_more_rows_has_no_cursor() {
+        let rows = vec![message(2, 20), message(1, 10)];
+        let (page, cursor) = take_page(rows.into_iter(), 2).unwrap();
+        assert_eq!(ids(&page), [2, 1]);
+        assert_eq!(cursor, None);
+    }
+
+    #[test]
+    fn seeded_content_is_deterministic() {
+        let draw = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..100)
+                .map(|_| (seed_content(&mut rng), seed_timestamp(&mut rng, at(0))))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(draw(42), draw(42));
+        assert_ne!(draw(42), draw(43));
+    }
+
+    #[test]
+    fn seed_content_is_alphanumeric_within_length_tiers() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..1_000 {
+            let content = seed_content(&mut rng);
+            assert!((10..4000).contains(&content.len()), "{}", content.len());
+            assert!(content.chars().all(|c| c.is_ascii_alphanumeric()));
+        }
+    }
+
+    #[test]
+    fn legacy_event_payload_reads_typed_json() {
+        assert_eq!(
+            legacy_event_payload(r#"{"value": 1.5, "unit": "ms"}"#),
+            EventPayload {
+                value: 1.5,
+                unit: Some("ms".to_string()),
+                tags: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn legacy_event_payload_keeps_free_form_json_as_tag() {
+        for data in [r#"{"data": 123}"#, "not json", r#"{"value": "high"}"#] {
+            assert_eq!(
+                legacy_event_payload(data),
+                EventPayload {
+                    value: 0.0,
+                    unit: None,
+                    tags: vec![data.to_string()],
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn seed_timestamp_stays_in_window() {
+        let now = at(SEED_WINDOW_MICROS * 2);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..1_000 {
+            let timestamp = seed_timestamp(&mut rng, now);
+            assert!(timestamp <= now);
+            assert!(timestamp > micros_after(now, -SEED_WINDOW_MICROS));
+        }
+    }
+
+    #[cfg(feature = "reducer-latency")]
+    #[test]
+    fn latency_bucket_is_power_of_two() {
+        assert_eq!(latency_bucket(0), 0);
+        assert_eq!(latency_bucket(1), 1);
+        assert_eq!(latency_bucket(2), 2);
+        assert_eq!(latency_bucket(3), 2);
+        assert_eq!(latency_bucket(4), 3);
+        assert_eq!(latency_bucket(1023), 10);
+        assert_eq!(latency_bucket(1024), 11);
+        assert_eq!(latency_bucket(u64::MAX), LATENCY_BUCKETS - 1);
+    }
 }